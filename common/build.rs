@@ -0,0 +1,37 @@
+use std::{fs, path::Path};
+
+/**
+ * Fails the build when a shader's compiled outputs are stale.
+ *
+ * src/shaders/compile.sh (shadercross) turns every .hlsl in src/shaders into the .spv/.msl we
+ * embed, and keeps a copy of the source it compiled next to them (compiled/<name>.hlsl).
+ * Timestamps don't survive a checkout, so the sources are compared byte for byte instead.
+ */
+fn main() {
+    let shaders = Path::new("src/shaders");
+    println!("cargo:rerun-if-changed={}", shaders.display());
+
+    let mut stale = Vec::new();
+    for entry in fs::read_dir(shaders).expect("Missing src/shaders") {
+        let source = entry.unwrap().path();
+        if source
+            .extension()
+            .is_none_or(|extension| extension != "hlsl")
+        {
+            continue;
+        }
+        let compiled = shaders.join("compiled").join(source.file_name().unwrap());
+        println!("cargo:rerun-if-changed={}", compiled.display());
+        if fs::read(&compiled).ok() != fs::read(&source).ok() {
+            stale.push(source.display().to_string());
+        }
+    }
+
+    if !stale.is_empty() {
+        stale.sort();
+        panic!(
+            "Compiled shaders are out of date with {}, run src/shaders/compile.sh and commit src/shaders/compiled",
+            stale.join(", ")
+        );
+    }
+}
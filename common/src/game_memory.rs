@@ -2,11 +2,38 @@ use std::ffi::c_void;
 
 use sdl3::sys::stdinc::{SDL_free, SDL_malloc};
 
+use crate::graphics::texture::Texture;
+
+/**
+ * Bump whenever the interface between the host and the game dll changes:
+ * exported symbols, their signatures, or the layout of GameMemory itself.
+ * The host refuses to call into a dll built against a different version.
+ */
+pub const GAME_ABI_VERSION: u32 = 5;
+
+/**
+ * Returned by the `game_abi` symbol every game dll exports.
+ *
+ * `layout_version` describes how the game lays out its state inside GameMemory::storage.
+ * When it changes across a hot reload the old state can't be reinterpreted by the new code,
+ * so it goes through GameMemory::snapshot instead (see `on_unload` / `migrate`).
+ */
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameAbi {
+    pub abi_version: u32,
+    pub layout_version: u64,
+}
+
 #[repr(C)]
 pub struct GameMemory {
     pub initialized: bool,
     pub storage: *mut c_void,
     pub quit: bool,
+    // Serialized game state, written by the game on unload and read back when it (re)initializes.
+    pub snapshot: Vec<u8>,
+    // The game's own image before it is scaled to the screen, set by every render for host captures
+    pub game_target: Option<Texture>,
 }
 
 impl GameMemory {
@@ -25,6 +52,8 @@ impl GameMemory {
             initialized: false,
             storage,
             quit: false,
+            snapshot: Vec::new(),
+            game_target: None,
         }
     }
 }
@@ -1,25 +1,74 @@
 use std::f32::consts::TAU;
+use std::ffi::c_void;
 use std::fmt::Debug;
 
-use sdl3::gpu::{BufferBinding, Device};
+use sdl3::gpu::{BufferBinding, PrimitiveType};
+use sdl3::sys::gpu::{
+    SDL_PushGPUFragmentUniformData, SDL_PushGPUVertexUniformData, SDL_SetGPUStencilReference,
+};
+use sdl3::rect::Rect;
 
+use crate::graphics::device::Device;
 use crate::graphics::material::Material;
 use crate::graphics::mesh::Mesh;
-use crate::graphics::render_target::RenderTarget;
+use crate::graphics::rasterizer::rasterize;
+use crate::graphics::recording::{RecordedDraw, RecordedPass};
+use crate::graphics::render_target::{LoadAction, RenderTarget};
 use crate::graphics::subtexture::Subtexture;
 use crate::graphics::texture::Texture;
-use crate::graphics::{IDENTITY, Vertex};
+use crate::graphics::{IDENTITY, MAX_INDICES, MAX_VERTICES, Vertex};
 
 pub struct Batch {
-    device: Device,
-    mesh: Mesh,
+    backend: Backend,
     default_material: Material,
     // TODO: All these Vec will allocate dynamically repace with array or pre-allocate them?
     vertices: Vec<Vertex>,
     indices: Vec<u32>,
     matrix_stack: Vec<glm::Mat4>,
     material_stack: Vec<Material>,
+    layer_stack: Vec<Layer>,
+    scissor_stack: Vec<Option<Rect>>,
     batches: Vec<DrawBatch>,
+    stats: BatchStats,
+}
+
+/**
+ * Sort key of a draw, lower layers are drawn first (behind).
+ * Within a layer, draws keep their submission order: one only joins an earlier draw with the same
+ * material and texture when nothing drawn in between overlaps it (see Batch::sort_layers).
+ * Games name their own layers: `const PLAYER: Layer = Layer(10);`
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Layer(pub i16);
+
+impl Layer {
+    pub const DEFAULT: Layer = Layer(0);
+}
+
+/**
+ * What the draw_into calls since the last Batch::take_stats submitted,
+ * and how close the largest of them came to the mesh capacity.
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BatchStats {
+    pub flushes: u32,
+    pub draws: u32,
+    pub peak_vertices: u32,
+    pub peak_indices: u32,
+    pub vertex_capacity: u32,
+    pub index_capacity: u32,
+}
+
+impl BatchStats {
+    /**
+     * Fraction (0..1) of the vertex or index budget used by the largest flush, whichever is higher.
+     * Above 1 the GPU buffers had to grow.
+     */
+    pub fn budget_used(&self) -> f32 {
+        let vertices = self.peak_vertices as f32 / self.vertex_capacity.max(1) as f32;
+        let indices = self.peak_indices as f32 / self.index_capacity.max(1) as f32;
+        return vertices.max(indices);
+    }
 }
 
 impl Debug for Batch {
@@ -32,21 +81,53 @@ impl Debug for Batch {
     }
 }
 
+enum Backend {
+    Gpu {
+        device: sdl3::gpu::Device,
+        mesh: Mesh,
+    },
+    // No GPU: draw_into records what it would have drawn instead
+    Headless {
+        passes: Vec<RecordedPass>,
+    },
+}
+
 impl Batch {
     pub fn new(device: Device, default_material: Material) -> Self {
+        let backend = match device {
+            Device::Gpu(device) => Backend::Gpu {
+                mesh: Mesh::new(device.clone()),
+                device,
+            },
+            Device::Headless => Backend::Headless {
+                passes: Default::default(),
+            },
+        };
         Batch {
-            device: device.clone(),
-            mesh: Mesh::new(device),
+            backend,
             default_material: default_material,
             vertices: Default::default(),
             indices: Default::default(),
             matrix_stack: Default::default(),
             material_stack: Default::default(),
+            layer_stack: Default::default(),
+            scissor_stack: Default::default(),
             batches: Default::default(),
+            stats: Default::default(),
         }
     }
 
+    /**
+     * Draws with `material` until the matching pop_material.
+     * Panics if one of its texture slots has nothing bound (see Material::set_texture).
+     */
     pub fn push_material(&mut self, material: &Material) {
+        if let Some(slot) = material.unbound_texture_slot() {
+            panic!(
+                "Material {}: nothing bound to {}, see Material::set_texture",
+                material.name, slot
+            );
+        }
         let current_material = self.current_batch().material.clone();
         self.material_stack.push(current_material);
         let current: &mut DrawBatch = self.current_batch();
@@ -66,6 +147,138 @@ impl Batch {
         return material;
     }
 
+    pub fn push_layer(&mut self, layer: Layer) {
+        let current_layer = self.current_batch().layer;
+        self.layer_stack.push(current_layer);
+        self.set_layer(layer);
+    }
+
+    pub fn pop_layer(&mut self) -> Layer {
+        let layer = self.layer_stack.pop().unwrap();
+        self.set_layer(layer);
+        return layer;
+    }
+
+    fn set_layer(&mut self, layer: Layer) {
+        let current: &mut DrawBatch = self.current_batch();
+        if current.elements > 0 && current.layer != layer {
+            self.push_batch();
+        }
+        self.current_batch().layer = layer;
+    }
+
+    /**
+     * Clips everything drawn until the matching pop_scissor to `rect`, in the target's
+     * width x height units (the matrix stack doesn't apply to it, draw_into scales it to the
+     * target's pixels). Nested scissors clip to their intersection.
+     */
+    pub fn push_scissor(&mut self, rect: Rect) {
+        let current_scissor = self.current_batch().scissor;
+        self.scissor_stack.push(current_scissor);
+        let scissor = match current_scissor {
+            Some(current) => current.intersection(rect).unwrap_or_else(|| {
+                // Nothing left to draw, but SDL rects can't be empty: zero the size by hand
+                let mut empty = rect;
+                empty.w = 0;
+                empty.h = 0;
+                empty
+            }),
+            None => rect,
+        };
+        self.set_scissor(Some(scissor));
+    }
+
+    pub fn pop_scissor(&mut self) -> Option<Rect> {
+        let scissor = self.scissor_stack.pop().unwrap();
+        self.set_scissor(scissor);
+        return scissor;
+    }
+
+    fn set_scissor(&mut self, scissor: Option<Rect>) {
+        let current: &mut DrawBatch = self.current_batch();
+        if current.elements > 0 && current.scissor != scissor {
+            self.push_batch();
+        }
+        self.current_batch().scissor = scissor;
+    }
+
+    /**
+     * Stable-sorts the draws by layer, then merges each draw into the latest earlier one of its
+     * layer sharing its material, texture and scissor, unless something drawn in between overlaps
+     * it: overlapping draws keep their painter order. Rebuilds the index buffer to match,
+     * vertices don't move.
+     * draw_into does this before submitting, call it before `record` to inspect the same order.
+     */
+    pub fn sort_layers(&mut self) {
+        self.batches.retain(|batch| batch.elements > 0);
+        if self.batches.len() < 2 {
+            return;
+        }
+
+        let bounds: Vec<Bounds> = self
+            .batches
+            .iter()
+            .map(|batch| Bounds::of(&self.vertices, self.batch_indices(batch)))
+            .collect();
+        let mut order: Vec<usize> = (0..self.batches.len()).collect();
+        order.sort_by_key(|&index| self.batches[index].layer); // Stable
+
+        // Sorted draws: the batches merged into each of them, and the area they cover
+        let mut draws: Vec<(Vec<usize>, Bounds)> = Vec::new();
+        let mut layer_start = 0;
+        for index in order {
+            let batch = &self.batches[index];
+            if let Some((parts, _)) = draws.last()
+                && self.batches[parts[0]].layer != batch.layer
+            {
+                layer_start = draws.len();
+            }
+
+            let mut merge_into = None;
+            for (position, (parts, covered)) in draws.iter().enumerate().skip(layer_start).rev() {
+                let draw = &self.batches[parts[0]];
+                if draw.material == batch.material
+                    && draw.texture == batch.texture
+                    && draw.scissor == batch.scissor
+                {
+                    merge_into = Some(position);
+                    break;
+                }
+                if covered.overlaps(&bounds[index]) {
+                    break; // Can't move under it
+                }
+            }
+            match merge_into {
+                Some(position) => {
+                    let (parts, covered) = &mut draws[position];
+                    parts.push(index);
+                    *covered = covered.union(&bounds[index]);
+                }
+                None => draws.push((vec![index], bounds[index])),
+            }
+        }
+
+        let mut unsorted: Vec<Option<DrawBatch>> = self.batches.drain(..).map(Some).collect();
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for (parts, _) in draws {
+            let offset = indices.len() as i64;
+            for &part in &parts {
+                let part = unsorted[part].as_ref().unwrap();
+                indices.extend_from_slice(self.batch_indices(part));
+            }
+            let mut batch = unsorted[parts[0]].take().unwrap();
+            batch.offset = offset;
+            batch.elements = indices.len() as i64 - offset;
+            self.batches.push(batch);
+        }
+        self.indices = indices;
+    }
+
+    fn batch_indices(&self, batch: &DrawBatch) -> &[u32] {
+        let start = batch.offset as usize;
+        return &self.indices[start..start + batch.elements as usize];
+    }
+
     pub fn push_matrix(&mut self, matrix: glm::Mat4) {
         if self.matrix_stack.is_empty() {
             self.matrix_stack.push(matrix);
@@ -116,7 +329,35 @@ impl Batch {
         self.push_vertex(position0, color, [0f32, 0f32], 0, 0, 255);
         self.push_vertex(position1, color, [0f32, 0f32], 0, 0, 255);
         self.push_vertex(position2, color, [0f32, 0f32], 0, 0, 255);
-        self.current_batch().elements += 1;
+        self.current_batch().elements += 3;
+    }
+
+    /**
+     * A 1px line under a LineList material, otherwise a quad `thickness` wide.
+     */
+    pub fn line(&mut self, from: [f32; 3], to: [f32; 3], thickness: f32, color: [u8; 4]) {
+        if self.current_batch().material.state.primitive_type == PrimitiveType::LineList {
+            let last_vertex_index = self.vertices.len() as u32;
+            self.indices
+                .extend([0 + last_vertex_index, 1 + last_vertex_index]);
+            self.push_vertex(from, color, [0f32, 0f32], 0, 0, 255);
+            self.push_vertex(to, color, [0f32, 0f32], 0, 0, 255);
+            self.current_batch().elements += 2;
+            return;
+        }
+
+        let direction = glm::vec2(to[0] - from[0], to[1] - from[1]);
+        if direction.magnitude() == 0f32 {
+            return;
+        }
+        let normal = glm::vec2(-direction.y, direction.x).normalize() * (thickness / 2f32);
+        self.quad(
+            [from[0] + normal.x, from[1] + normal.y, from[2]],
+            [to[0] + normal.x, to[1] + normal.y, to[2]],
+            [from[0] - normal.x, from[1] - normal.y, from[2]],
+            [to[0] - normal.x, to[1] - normal.y, to[2]],
+            color,
+        );
     }
 
     // TODO: why is this taking a glm::2 as position (and not [f32;3])
@@ -355,41 +596,115 @@ impl Batch {
         }
     }
 
+    /**
+     * Submits everything drawn since the last clear into `target`, loading it with the
+     * target's own load_action (LoadAction::Clear unless changed).
+     */
     pub fn draw_into(&mut self, target: &RenderTarget) {
+        self.draw_into_with(target, target.load_action);
+    }
+
+    /**
+     * draw_into with another LoadAction for this pass only. LoadAction::Load draws on top of
+     * what earlier passes left in the target, so several batches can build one image:
+     *
+     *   tiles.draw_into(&target);                           // Clears
+     *   entities.draw_into_with(&target, LoadAction::Load); // Keeps the tiles
+     */
+    pub fn draw_into_with(&mut self, target: &RenderTarget, load_action: LoadAction) {
         // println!("{:#?}", self);
+        self.sort_layers();
+        for batch in self.batches.iter().filter(|batch| batch.elements > 0) {
+            let state = &batch.material.state;
+            let depth_stencil_format = state
+                .depth_stencil
+                .map(|depth_stencil| depth_stencil.format);
+            assert!(
+                state.sample_count == target.sample_count()
+                    && depth_stencil_format == target.depth_stencil_format(),
+                "Material {} ({:?}, depth-stencil {:?}) can't draw into a target with {:?}, depth-stencil {:?}",
+                batch.material.name,
+                state.sample_count,
+                depth_stencil_format,
+                target.sample_count(),
+                target.depth_stencil_format()
+            );
+        }
+
+        // Measured against the capacity before this flush, so a flush that forces a grow reads > 100%
+        let (vertex_capacity, index_capacity) = match &self.backend {
+            Backend::Gpu { mesh, .. } => (mesh.vertex_capacity(), mesh.index_capacity()),
+            Backend::Headless { .. } => (MAX_VERTICES, MAX_INDICES),
+        };
+        self.stats.flushes += 1;
+        self.stats.draws += self.get_batch_count() as u32;
+        self.stats.peak_vertices = self.stats.peak_vertices.max(self.vertices.len() as u32);
+        self.stats.peak_indices = self.stats.peak_indices.max(self.indices.len() as u32);
+        self.stats.vertex_capacity = vertex_capacity;
+        self.stats.index_capacity = index_capacity;
+
+        let (device, mesh) = match &mut self.backend {
+            Backend::Gpu { device, mesh } => (device, mesh),
+            Backend::Headless { .. } => {
+                let pass = self.record(target, load_action);
+                // Offscreen targets have CPU pixels to draw into, the headless screen has none
+                if let Some(mut pixels) = pass.target.as_ref().and_then(|t| t.pixels_mut()) {
+                    rasterize(&pass, &mut pixels);
+                }
+                if let Backend::Headless { passes } = &mut self.backend {
+                    passes.push(pass);
+                }
+                return;
+            }
+        };
 
         // Copy pass
         {
-            let upload_cmd = self.device.acquire_command_buffer().unwrap();
-            let copy_pass = self.device.begin_copy_pass(&upload_cmd).unwrap();
-            for batch in &mut self.batches {
-                if let Some(texture) = batch.texture.as_mut() {
-                    texture.upload(&copy_pass);
+            let upload_cmd = device.acquire_command_buffer().unwrap();
+            let copy_pass = device.begin_copy_pass(&upload_cmd).unwrap();
+            for batch in &self.batches {
+                if let Some(texture) = batch.texture.as_ref() {
+                    texture.upload(device, &copy_pass);
                 }
+                batch.material.upload_textures(device, &copy_pass);
+                batch.material.reload_shaders(device);
             }
 
-            self.mesh.set_data(&self.vertices);
-            self.mesh.set_indices(&self.indices);
-            self.mesh.upload(&copy_pass);
+            mesh.set_data(&self.vertices);
+            mesh.set_indices(&self.indices);
+            mesh.upload(&copy_pass);
 
-            self.device.end_copy_pass(copy_pass);
+            device.end_copy_pass(copy_pass);
+            for batch in &self.batches {
+                if let Some(texture) = batch.texture.as_ref() {
+                    texture.generate_mipmaps(&upload_cmd);
+                }
+                batch.material.generate_mipmaps(&upload_cmd);
+            }
             upload_cmd.submit().unwrap();
         }
 
         // Render pass
         {
-            let render_cmd = self.device.acquire_command_buffer().unwrap();
-            let render_pass = self
-                .device
-                .begin_render_pass(&render_cmd, &[target.color_target_info()], None)
+            let render_cmd = device.acquire_command_buffer().unwrap();
+            let depth_stencil = target.depth_stencil_target_info(load_action);
+            let render_pass = device
+                .begin_render_pass(
+                    &render_cmd,
+                    &[target.color_target_info(load_action)],
+                    depth_stencil.as_ref(),
+                )
                 .unwrap();
+            if depth_stencil.is_some() {
+                unsafe { SDL_SetGPUStencilReference(render_pass.raw(), target.stencil_reference) };
+            }
 
             let buffer_binding = BufferBinding::new()
                 .with_offset(0)
-                .with_buffer(&self.mesh.vertex_buffer);
+                .with_buffer(&mesh.vertex_buffer);
             let index_binding = BufferBinding::new()
                 .with_offset(0)
-                .with_buffer(&self.mesh.index_buffer);
+                .with_buffer(&mesh.index_buffer);
 
             render_cmd.push_vertex_uniform_data(0, target.projection());
 
@@ -404,20 +719,58 @@ impl Batch {
                     // TODO: Is adding an empty batch needed?
                     break;
                 }
+                let Some(scissor) = pass_scissor(target, batch.scissor) else {
+                    continue; // Clipped away entirely
+                };
+                render_pass.set_scissor(scissor);
                 if let Some(texture) = &batch.texture {
                     render_pass.bind_fragment_samplers(0, &[texture.bindings()]);
                 }
-                render_pass.bind_graphics_pipeline(&batch.material.pipeline);
+                // push_material checked every slot is bound
+                for (slot, _, texture) in batch.material.slot_textures() {
+                    if let Some(texture) = texture {
+                        render_pass.bind_fragment_samplers(slot, &[texture.bindings()]);
+                    }
+                }
+                if let Some(pipeline) = batch.material.pipeline() {
+                    render_pass.bind_graphics_pipeline(&pipeline);
+                }
+                // Uniform data belongs to the command buffer, and applies to the draws that follow
+                for uniform in &batch.material.vertex_uniforms {
+                    unsafe {
+                        SDL_PushGPUVertexUniformData(
+                            render_cmd.raw(),
+                            uniform.slot,
+                            uniform.bytes().as_ptr() as *const c_void,
+                            uniform.bytes().len() as u32,
+                        );
+                    }
+                }
+                for uniform in &batch.material.fragment_uniforms {
+                    unsafe {
+                        SDL_PushGPUFragmentUniformData(
+                            render_cmd.raw(),
+                            uniform.slot,
+                            uniform.bytes().as_ptr() as *const c_void,
+                            uniform.bytes().len() as u32,
+                        );
+                    }
+                }
                 render_pass.draw_indexed_primitives(
-                    (batch.elements * 3) as u32,
+                    batch.elements as u32,
                     1,
-                    (batch.offset * 3) as u32,
+                    batch.offset as u32,
                     0,
                     0,
                 );
             }
 
-            self.device.end_render_pass(render_pass);
+            device.end_render_pass(render_pass);
+            if !target.is_screen() {
+                let texture = target.color();
+                texture.mark_mipmaps_stale();
+                texture.generate_mipmaps(&render_cmd);
+            }
             render_cmd.submit().unwrap();
         }
     }
@@ -457,7 +810,7 @@ impl Batch {
         self.push_vertex(position1, color, uv1, mult, wash, fill);
         self.push_vertex(position2, color, uv2, mult, wash, fill);
         self.push_vertex(position3, color, uv3, mult, wash, fill);
-        self.current_batch().elements += 2;
+        self.current_batch().elements += 6;
     }
 
     fn current_batch(&mut self) -> &mut DrawBatch {
@@ -467,6 +820,8 @@ impl Batch {
                 elements: 0,
                 material: self.default_material.clone(),
                 texture: None,
+                layer: Layer::DEFAULT,
+                scissor: None,
             };
             self.batches.push(value);
         }
@@ -493,6 +848,68 @@ impl Batch {
         });
     }
 
+    /**
+     * Captures what draw_into(target) would submit (once sorted, see sort_layers), with any Device.
+     * Used by the headless backend, and to inspect a batch without a GPU.
+     */
+    pub fn record(&self, target: &RenderTarget, load_action: LoadAction) -> RecordedPass {
+        let draws = self
+            .batches
+            .iter()
+            .take_while(|batch| batch.elements > 0)
+            .map(|batch| {
+                let first_index = batch.offset as usize;
+                let index_count = batch.elements as usize;
+                let indices = &self.indices[first_index..first_index + index_count];
+                let first_vertex = *indices.iter().min().unwrap();
+                let last_vertex = *indices.iter().max().unwrap();
+                RecordedDraw {
+                    material: batch.material.name,
+                    slot_textures: batch
+                        .material
+                        .slot_textures()
+                        .map(|(_, name, texture)| (name, texture.cloned()))
+                        .collect(),
+                    blend_mode: batch.material.state.blend_mode,
+                    primitive_type: batch.material.state.primitive_type,
+                    texture: batch.texture.clone(),
+                    scissor: batch.scissor,
+                    first_index: first_index as u32,
+                    index_count: indices.len() as u32,
+                    first_vertex,
+                    vertex_count: last_vertex - first_vertex + 1,
+                }
+            })
+            .collect();
+
+        return RecordedPass {
+            target: (!target.is_screen()).then(|| target.color()),
+            target_width: target.width,
+            target_height: target.height,
+            clear_color: target.clear_color(),
+            load_action,
+            projection: *target.projection(),
+            vertices: self.vertices.clone(),
+            indices: self.indices.clone(),
+            draws,
+        };
+    }
+
+    /**
+     * Headless only: every draw_into since the last call, in submission order.
+     * Always empty when rendering with a GPU.
+     */
+    pub fn take_recorded_passes(&mut self) -> Vec<RecordedPass> {
+        match &mut self.backend {
+            Backend::Gpu { .. } => Vec::new(),
+            Backend::Headless { passes } => std::mem::take(passes),
+        }
+    }
+
+    pub fn take_stats(&mut self) -> BatchStats {
+        std::mem::take(&mut self.stats)
+    }
+
     pub fn get_batch_count(&self) -> usize {
         self.batches.iter().filter(|b| b.elements > 0).count()
     }
@@ -503,12 +920,262 @@ impl Batch {
         self.indices.clear();
         self.matrix_stack.clear();
         self.material_stack.clear();
+        self.layer_stack.clear();
+        self.scissor_stack.clear();
+    }
+}
+
+/**
+ * The scissor a batch is drawn with, in the target's pixels: the whole target when unclipped
+ * (the scissor is pass state, it has to be reset), None when clipped away entirely.
+ */
+fn pass_scissor(target: &RenderTarget, scissor: Option<Rect>) -> Option<Rect> {
+    let (width, height) = target.pixel_size();
+    let full_target = Rect::new(0, 0, width, height);
+    return match scissor {
+        Some(scissor) => target.to_pixels(scissor).intersection(full_target),
+        None => Some(full_target),
+    };
+}
+
+/**
+ * Axis-aligned box around a draw's vertices, in the space they're stored in (before projection).
+ */
+#[derive(Clone, Copy)]
+struct Bounds {
+    min: glm::Vec2,
+    max: glm::Vec2,
+}
+
+impl Bounds {
+    fn of(vertices: &[Vertex], indices: &[u32]) -> Self {
+        let mut bounds = Bounds {
+            min: glm::vec2(f32::INFINITY, f32::INFINITY),
+            max: glm::vec2(f32::NEG_INFINITY, f32::NEG_INFINITY),
+        };
+        for &index in indices {
+            let position = vertices[index as usize].position;
+            let position = glm::vec2(position[0], position[1]);
+            bounds.min = glm::min2(&bounds.min, &position);
+            bounds.max = glm::max2(&bounds.max, &position);
+        }
+        return bounds;
+    }
+
+    fn union(&self, other: &Bounds) -> Bounds {
+        return Bounds {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        };
+    }
+
+    // Boxes that only share an edge don't overlap, like tiles of a tilemap
+    fn overlaps(&self, other: &Bounds) -> bool {
+        return self.min.x < other.max.x
+            && other.min.x < self.max.x
+            && self.min.y < other.max.y
+            && other.min.y < self.max.y;
     }
 }
 
 pub struct DrawBatch {
+    // Both in indices: 3 per triangle, 2 per line
     offset: i64,
     elements: i64,
     material: Material,
     texture: Option<Texture>,
+    layer: Layer,
+    // Target width x height units, None draws unclipped
+    scissor: Option<Rect>,
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl3::gpu::TextureFormat;
+
+    use super::*;
+    use crate::graphics::material::{
+        MaterialSpecification, PipelineSpecification, ShaderSpecification,
+    };
+
+    const SHADER: ShaderSpecification = ShaderSpecification {
+        msl: None,
+        spirv: None,
+        dxil: None,
+        uniform_buffer_count: 0,
+        sampler_count: 2,
+    };
+
+    // The draw's texture in sampler 0, "palette" in sampler 1
+    const PALETTE_MATERIAL: MaterialSpecification = MaterialSpecification {
+        name: "palette",
+        fragment: SHADER,
+        vertex: SHADER,
+        texture_format: TextureFormat::R8g8b8a8Unorm,
+        pipeline: PipelineSpecification::DEFAULT,
+        texture_slots: &["palette"],
+        source: None,
+        hot_reload: None,
+    };
+
+    fn texture() -> Texture {
+        Texture::new(Device::Headless, 8, 8, TextureFormat::R8g8b8a8Unorm)
+    }
+
+    // (texture id, index count) of every draw, in the order draw_into submits them
+    fn sorted_draws(draw: impl FnOnce(&mut Batch)) -> Vec<(Option<u16>, u32)> {
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        draw(&mut batch);
+        batch.sort_layers();
+        let mut target = RenderTarget::empty();
+        target.resize(64, 64);
+        return batch
+            .record(&target, LoadAction::Clear)
+            .draws
+            .iter()
+            .map(|draw| (draw.texture.as_ref().map(|t| t.id), draw.index_count))
+            .collect();
+    }
+
+    #[test]
+    fn overlapping_draws_keep_their_painter_order() {
+        let (window, preview) = (texture(), texture());
+        let draws = sorted_draws(|batch| {
+            batch.texture(window.clone(), &glm::vec2(0f32, 0f32));
+            batch.texture(preview.clone(), &glm::vec2(4f32, 4f32));
+            batch.texture(window.clone(), &glm::vec2(8f32, 8f32));
+        });
+        // Merging the second window into the first would draw it under the preview
+        assert_eq!(
+            draws,
+            [
+                (Some(window.id), 6),
+                (Some(preview.id), 6),
+                (Some(window.id), 6)
+            ]
+        );
+    }
+
+    #[test]
+    fn draws_that_dont_overlap_merge() {
+        let (tiles, player) = (texture(), texture());
+        let draws = sorted_draws(|batch| {
+            batch.texture(tiles.clone(), &glm::vec2(0f32, 0f32));
+            batch.texture(player.clone(), &glm::vec2(32f32, 0f32));
+            // Shares an edge with the first tile, and nothing in between overlaps it
+            batch.texture(tiles.clone(), &glm::vec2(8f32, 0f32));
+            batch.texture(player.clone(), &glm::vec2(32f32, 32f32));
+        });
+        assert_eq!(draws, [(Some(tiles.id), 12), (Some(player.id), 12)]);
+    }
+
+    #[test]
+    fn draws_only_merge_past_what_they_dont_overlap() {
+        let (a, b, c) = (texture(), texture(), texture());
+        let draws = sorted_draws(|batch| {
+            batch.texture(a.clone(), &glm::vec2(0f32, 0f32));
+            batch.texture(b.clone(), &glm::vec2(16f32, 0f32));
+            batch.texture(c.clone(), &glm::vec2(32f32, 0f32));
+            // Free to join `b`, but `c` is in the way of joining `a`
+            batch.texture(b.clone(), &glm::vec2(16f32, 16f32));
+            batch.texture(a.clone(), &glm::vec2(36f32, 4f32));
+        });
+        assert_eq!(
+            draws,
+            [
+                (Some(a.id), 6),
+                (Some(b.id), 12),
+                (Some(c.id), 6),
+                (Some(a.id), 6)
+            ]
+        );
+    }
+
+    #[test]
+    fn layers_sort_before_painter_order() {
+        let (front, back) = (texture(), texture());
+        let draws = sorted_draws(|batch| {
+            batch.push_layer(Layer(1));
+            batch.texture(front.clone(), &glm::vec2(0f32, 0f32));
+            batch.pop_layer();
+            batch.texture(back.clone(), &glm::vec2(0f32, 0f32));
+            batch.push_layer(Layer(1));
+            batch.texture(front.clone(), &glm::vec2(4f32, 4f32));
+            batch.pop_layer();
+        });
+        assert_eq!(draws, [(Some(back.id), 6), (Some(front.id), 12)]);
+    }
+
+    #[test]
+    fn merged_draws_keep_their_indices() {
+        let (tiles, player) = (texture(), texture());
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        batch.texture(tiles.clone(), &glm::vec2(0f32, 0f32));
+        batch.texture(player.clone(), &glm::vec2(32f32, 0f32));
+        batch.texture(tiles.clone(), &glm::vec2(8f32, 0f32));
+        batch.sort_layers();
+        assert_eq!(
+            batch.indices,
+            [0, 1, 2, 2, 1, 3, 8, 9, 10, 10, 9, 11, 4, 5, 6, 6, 5, 7]
+        );
+    }
+
+    #[test]
+    fn full_target_covers_the_swapchain_texture() {
+        // A 2x display: the window is 640x360, its swapchain texture 1280x720
+        let mut screen = RenderTarget::empty();
+        screen.resize(640, 360);
+        screen.resize_pixels(1280, 720);
+        assert_eq!(
+            pass_scissor(&screen, None),
+            Some(Rect::new(0, 0, 1280, 720))
+        );
+        assert_eq!(
+            pass_scissor(&screen, Some(Rect::new(10, 20, 30, 40))),
+            Some(Rect::new(20, 40, 60, 80))
+        );
+        assert_eq!(
+            pass_scissor(&screen, Some(Rect::new(600, 0, 100, 360))),
+            Some(Rect::new(1200, 0, 80, 720))
+        );
+        assert_eq!(pass_scissor(&screen, Some(Rect::new(700, 0, 10, 10))), None);
+    }
+
+    #[test]
+    fn texture_slots_are_bound_per_draw() {
+        let (sprite, day, night) = (texture(), texture(), texture());
+        let mut material = Material::from_specification(Device::Headless, &PALETTE_MATERIAL);
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        material.set_texture("palette", day.clone());
+        batch.push_material(&material);
+        batch.texture(sprite.clone(), &glm::vec2(0f32, 0f32));
+        batch.pop_material();
+        material.set_texture("palette", night.clone());
+        batch.push_material(&material);
+        batch.texture(sprite.clone(), &glm::vec2(0f32, 0f32));
+        batch.pop_material();
+
+        let mut target = RenderTarget::empty();
+        target.resize(64, 64);
+        batch.sort_layers();
+        let pass = batch.record(&target, LoadAction::Clear);
+        let palettes: Vec<_> = pass
+            .draws
+            .iter()
+            .flat_map(|draw| &draw.slot_textures)
+            .map(|(name, texture)| (*name, texture.as_ref().map(|t| t.id)))
+            .collect();
+        assert_eq!(
+            palettes,
+            [("palette", Some(day.id)), ("palette", Some(night.id))]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "nothing bound to palette")]
+    fn unbound_texture_slots_panic_when_pushed() {
+        let material = Material::from_specification(Device::Headless, &PALETTE_MATERIAL);
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        batch.push_material(&material);
+    }
 }
@@ -0,0 +1,11 @@
+/**
+ * Handle to whatever the graphics types render with.
+ *
+ * `Headless` has no GPU at all (CI machines, benchmarks): textures keep their pixels
+ * on the CPU and Batch records its draws instead of submitting them.
+ */
+#[derive(Clone)]
+pub enum Device {
+    Gpu(sdl3::gpu::Device),
+    Headless,
+}
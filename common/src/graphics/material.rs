@@ -1,15 +1,28 @@
 use sdl3::{
     gpu::{
-        BlendFactor, ColorTargetBlendState, ColorTargetDescription, Device, GraphicsPipeline,
-        GraphicsPipelineTargetInfo, PrimitiveType, Shader, ShaderFormat, ShaderStage,
-        TextureFormat, VertexAttribute, VertexBufferDescription, VertexElementFormat,
-        VertexInputRate, VertexInputState,
+        BlendFactor, BlendOp, ColorTargetBlendState, ColorTargetDescription, CommandBuffer,
+        CompareOp, CopyPass, CullMode, DepthStencilState, FrontFace, GraphicsPipeline,
+        GraphicsPipelineBuilder, GraphicsPipelineTargetInfo, PrimitiveType, RasterizerState,
+        SampleCount, Shader, ShaderFormat, ShaderStage, StencilOp, StencilOpState, TextureFormat,
+        VertexAttribute, VertexBufferDescription, VertexElementFormat, VertexInputRate,
+        VertexInputState,
     },
+    sys::gpu::{SDL_GPUGraphicsPipelineCreateInfo, SDL_GPUSampleCount},
     video::Window,
 };
-use std::ffi::CStr;
+use std::{
+    cell::{Ref, RefCell},
+    ffi::CStr,
+    rc::Rc,
+};
 
-use crate::graphics::Vertex;
+use crate::graphics::{
+    Vertex,
+    device::Device,
+    shader_reflection::ShaderReflection,
+    shader_reload::{ShaderCompiler, ShaderHotReload, ShaderWatch},
+    texture::Texture,
+};
 
 static FS_ENTRY: &CStr = c"fragment_main";
 static VS_ENTRY: &CStr = c"vertex_main";
@@ -17,186 +30,699 @@ static VS_ENTRY: &CStr = c"vertex_main";
 #[derive(Clone)]
 pub struct Material {
     pub name: &'static str,
-    pub pipeline: GraphicsPipeline,
+    // None on a headless Device. Shared by every clone, so a shader reload swaps it for all of them
+    pipeline: Option<Rc<RefCell<GraphicsPipeline>>>,
+    shader_watch: Option<Rc<ShaderWatch>>,
     pub target_texture_format: TextureFormat,
+    // What `pipeline` was built with, kept on headless Devices too
+    pub state: PipelineSpecification,
+    // Pushed before every draw using this material, see set_vertex_uniform / set_fragment_uniform
+    pub vertex_uniforms: Vec<UniformBlock>,
+    pub fragment_uniforms: Vec<UniformBlock>,
+    // Fragment samplers 1.. (0 is the texture of the draw), see set_texture
+    pub texture_slots: &'static [&'static str],
+    textures: Vec<Option<Texture>>,
 }
 
 impl PartialEq for Material {
     fn eq(&self, other: &Self) -> bool {
-        self.pipeline.raw() == other.pipeline.raw()
+        self.pipeline.as_ref().map(Rc::as_ptr) == other.pipeline.as_ref().map(Rc::as_ptr)
             && self.name == other.name
+            && self.state == other.state
+            && self.vertex_uniforms == other.vertex_uniforms
+            && self.fragment_uniforms == other.fragment_uniforms
+            && self.textures == other.textures
+    }
+}
+
+/**
+ * The values of one `cbuffer`, copied as raw bytes.
+ * The Rust type must be #[repr(C)] and match the HLSL packing (pad to float4 boundaries):
+ *
+ *   cbuffer FragmentUniforms : register(b0, space3) { float4 Tint; float Time; };
+ *   #[repr(C)] struct FragmentUniforms { tint: [f32; 4], time: f32, _padding: [f32; 3] }
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct UniformBlock {
+    pub slot: u32,
+    bytes: Vec<u8>,
+}
+
+impl UniformBlock {
+    /**
+     * # Safety
+     * `T` must be #[repr(C)] without padding bytes, padding is uninitialized memory and
+     * can't be read. Pad explicitly with fields instead (`_padding` above).
+     */
+    pub unsafe fn new<T: Copy>(slot: u32, value: &T) -> Self {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()).to_vec()
+        };
+        return UniformBlock { slot, bytes };
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
     }
 }
 
-static DEFAULT_SHADER_FRAGMENT_SRC: &str = include_str!("../shaders/compiled/default.fragment.msl");
-static DEFAULT_SHADER_VERTEX_SRC: &str = include_str!("../shaders/compiled/default.vertex.msl");
+const DEFAULT_FRAGMENT: ShaderSpecification = ShaderSpecification {
+    msl: Some(include_str!("../shaders/compiled/default.fragment.msl")),
+    spirv: Some(include_bytes!("../shaders/compiled/default.fragment.spv")),
+    dxil: None,
+    // Reflected from DEFAULT_SOURCE
+    uniform_buffer_count: 0,
+    sampler_count: 0,
+};
+
+const DEFAULT_VERTEX: ShaderSpecification = ShaderSpecification {
+    msl: Some(include_str!("../shaders/compiled/default.vertex.msl")),
+    spirv: Some(include_bytes!("../shaders/compiled/default.vertex.spv")),
+    dxil: None,
+    // Reflected from DEFAULT_SOURCE
+    uniform_buffer_count: 0,
+    sampler_count: 0,
+};
+
+const DEFAULT_SOURCE: &str = include_str!("../shaders/default.hlsl");
+
+// (location, offset, format) of each Vertex field, in the order of VsInput's TEXCOORDn
+const VERTEX_ATTRIBUTES: [(u32, usize, VertexElementFormat); 4] = [
+    (
+        0,
+        std::mem::offset_of!(Vertex, position),
+        VertexElementFormat::Float3,
+    ),
+    (
+        1,
+        std::mem::offset_of!(Vertex, color),
+        VertexElementFormat::Ubyte4Norm,
+    ),
+    (
+        2,
+        std::mem::offset_of!(Vertex, texture_uv),
+        VertexElementFormat::Float2,
+    ),
+    (
+        3,
+        std::mem::offset_of!(Vertex, mult_wash_fill),
+        VertexElementFormat::Ubyte4Norm,
+    ),
+];
+
+// Debug builds only: release builds run away from the source tree
+const DEFAULT_HOT_RELOAD: Option<ShaderHotReload> = if cfg!(debug_assertions) {
+    Some(ShaderHotReload {
+        path: concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/default.hlsl"),
+        compiler: ShaderCompiler::SHADERCROSS,
+    })
+} else {
+    None
+};
 
 impl Material {
     pub fn default(device: Device, window: &Window) -> Self {
-        let supported_formats_bitflag = device.get_shader_formats();
+        let texture_format = match &device {
+            Device::Gpu(device) => device.get_swapchain_texture_format(&window),
+            Device::Headless => TextureFormat::R8g8b8a8Unorm,
+        };
+        return Self::default_with_state(
+            device,
+            "default",
+            texture_format,
+            PipelineSpecification::DEFAULT,
+        );
+    }
+
+    /**
+     * The default shaders (texture * vertex color) with other fixed-function state,
+     * e.g. a different BlendMode.
+     */
+    pub fn default_with_state(
+        device: Device,
+        name: &'static str,
+        texture_format: TextureFormat,
+        state: PipelineSpecification,
+    ) -> Self {
+        let Device::Gpu(device) = device else {
+            return Self::headless_with_state(name, state);
+        };
+        let (vertex, fragment) = reflect(DEFAULT_SOURCE, DEFAULT_VERTEX, DEFAULT_FRAGMENT)
+            .unwrap_or_else(|e| panic!("Material {}: {}", name, e));
+        let fs = fragment.create(&device, name, ShaderStage::Fragment);
+        let vs = vertex.create(&device, name, ShaderStage::Vertex);
 
-        let shader_format;
-        if (supported_formats_bitflag & ShaderFormat::MSL) == ShaderFormat::MSL {
-            shader_format = ShaderFormat::MSL
-        } else {
-            panic!("Shader format not supported")
+        let mut material = Self::new(name, device, vs, fs, texture_format, state);
+        if let Some(hot_reload) = DEFAULT_HOT_RELOAD {
+            material = material.with_shader_watch(hot_reload, vertex, fragment);
         }
+        return material;
+    }
 
-        let fs: Shader = device
-            .create_shader()
-            .with_samplers(1) // Texture
-            .with_code(
-                shader_format,
-                DEFAULT_SHADER_FRAGMENT_SRC.as_bytes(),
-                ShaderStage::Fragment,
-            )
-            .with_entrypoint(FS_ENTRY)
-            .build()
-            .expect("Unable to create fragment shader");
+    /**
+     * A material without pipeline, only its name and state are kept (for recorded draws).
+     */
+    pub fn headless(name: &'static str) -> Self {
+        Self::headless_with_state(name, PipelineSpecification::DEFAULT)
+    }
 
-        let vs: Shader = device
-            .create_shader()
-            .with_uniform_buffers(1) // Projection Matrix
-            .with_code(
-                shader_format,
-                DEFAULT_SHADER_VERTEX_SRC.as_bytes(),
-                ShaderStage::Vertex,
-            )
-            .with_entrypoint(VS_ENTRY)
-            .build()
-            .expect("Unable to create vertex shader");
+    fn headless_with_state(name: &'static str, state: PipelineSpecification) -> Self {
+        Material {
+            name,
+            pipeline: None,
+            shader_watch: None,
+            target_texture_format: TextureFormat::R8g8b8a8Unorm,
+            state,
+            vertex_uniforms: Vec::new(),
+            fragment_uniforms: Vec::new(),
+            texture_slots: &[],
+            textures: Vec::new(),
+        }
+    }
+
+    fn with_shader_watch(
+        mut self,
+        source: ShaderHotReload,
+        vertex: ShaderSpecification,
+        fragment: ShaderSpecification,
+    ) -> Self {
+        self.shader_watch = Some(Rc::new(ShaderWatch::new(source, vertex, fragment)));
+        return self;
+    }
+
+    /**
+     * None on a headless Device.
+     */
+    pub fn pipeline(&self) -> Option<Ref<'_, GraphicsPipeline>> {
+        self.pipeline.as_ref().map(|pipeline| pipeline.borrow())
+    }
+
+    /**
+     * Rebuilds the pipeline if the material's HLSL source changed (see ShaderHotReload),
+     * for every clone of this material at once. When compiling fails the error is printed
+     * and the previous pipeline is kept. Batch::draw_into calls this for the materials it draws.
+     */
+    pub fn reload_shaders(&self, device: &sdl3::gpu::Device) {
+        let (Some(watch), Some(pipeline)) = (&self.shader_watch, &self.pipeline) else {
+            return;
+        };
+        if !watch.poll() {
+            return;
+        }
+
+        println!("Recompiling {} shaders from {}", self.name, watch.path());
+        let rebuilt = watch.compile(device, self.name).and_then(|(vs, fs)| {
+            create_pipeline(device, &vs, &fs, self.target_texture_format, &self.state)
+                .map_err(|e| format!("Pipeline rejected: {}", e))
+        });
+        match rebuilt {
+            Ok(rebuilt) => {
+                *pipeline.borrow_mut() = rebuilt;
+                println!("Reloaded {} shaders", self.name);
+            }
+            Err(e) => eprintln!("Keeping the previous {} shaders:\n{}", self.name, e),
+        }
+    }
+
+    fn with_texture_slots(mut self, texture_slots: &'static [&'static str]) -> Self {
+        self.texture_slots = texture_slots;
+        self.textures = vec![None; texture_slots.len()];
+        return self;
+    }
 
-        let texture_format = device.get_swapchain_texture_format(&window);
+    /**
+     * Assigns the texture bound to the named slot of MaterialSpecification::texture_slots
+     * (e.g. "normal" or "palette"), next to the draw's own texture in sampler 0.
+     * Like uniforms, textures are part of the material's value: changing them splits batches.
+     */
+    pub fn set_texture(&mut self, slot: &str, texture: Texture) {
+        let Some(index) = self.texture_slots.iter().position(|name| *name == slot) else {
+            panic!("Material {} has no texture slot named {}", self.name, slot);
+        };
+        self.textures[index] = Some(texture);
+    }
+
+    pub fn upload_textures(&self, device: &sdl3::gpu::Device, pass: &CopyPass) {
+        for texture in self.textures.iter().flatten() {
+            texture.upload(device, pass);
+        }
+    }
+
+    pub(crate) fn generate_mipmaps(&self, command_buffer: &CommandBuffer) {
+        for texture in self.textures.iter().flatten() {
+            texture.generate_mipmaps(command_buffer);
+        }
+    }
 
-        return Self::new("default", device, vs, fs, texture_format);
+    /**
+     * The first texture slot set_texture hasn't assigned yet.
+     */
+    pub fn unbound_texture_slot(&self) -> Option<&'static str> {
+        return self
+            .texture_slots
+            .iter()
+            .zip(&self.textures)
+            .find(|(_, texture)| texture.is_none())
+            .map(|(name, _)| *name);
+    }
+
+    /**
+     * (fragment sampler slot, slot name, texture) of every slot, assigned or not.
+     */
+    pub fn slot_textures(&self) -> impl Iterator<Item = (u32, &'static str, Option<&Texture>)> {
+        self.texture_slots
+            .iter()
+            .zip(&self.textures)
+            .enumerate()
+            .map(|(index, (name, texture))| (index as u32 + 1, *name, texture.as_ref()))
+    }
+
+    /**
+     * Vertex uniform slot 0 holds the target projection (pushed by Batch::draw_into),
+     * material blocks start at slot 1.
+     *
+     * Batches compare materials by value: pushing a material whose uniforms changed
+     * starts a new draw call.
+     *
+     * # Safety
+     * `T` must meet UniformBlock::new's requirements.
+     */
+    pub unsafe fn set_vertex_uniform<T: Copy>(&mut self, slot: u32, value: &T) {
+        assert!(slot > 0, "Vertex uniform slot 0 is the projection matrix");
+        let block = unsafe { UniformBlock::new(slot, value) };
+        Self::set_uniform(&mut self.vertex_uniforms, block);
+    }
+
+    /**
+     * # Safety
+     * `T` must meet UniformBlock::new's requirements.
+     */
+    pub unsafe fn set_fragment_uniform<T: Copy>(&mut self, slot: u32, value: &T) {
+        let block = unsafe { UniformBlock::new(slot, value) };
+        Self::set_uniform(&mut self.fragment_uniforms, block);
+    }
+
+    fn set_uniform(uniforms: &mut Vec<UniformBlock>, block: UniformBlock) {
+        match uniforms
+            .iter_mut()
+            .find(|uniform| uniform.slot == block.slot)
+        {
+            Some(uniform) => *uniform = block,
+            None => uniforms.push(block),
+        }
     }
 
     pub fn new(
         name: &'static str,
-        device: Device,
+        device: sdl3::gpu::Device,
         vs: Shader,
         fs: Shader,
         target_texture_format: TextureFormat,
+        state: PipelineSpecification,
     ) -> Self {
-        let graphics_pipeline = device
-            .create_graphics_pipeline()
-            .with_target_info(
-                GraphicsPipelineTargetInfo::new().with_color_target_descriptions(&[
-                    ColorTargetDescription::new()
-                        .with_blend_state(
-                            ColorTargetBlendState::new()
-                                .with_src_color_blendfactor(BlendFactor::SrcAlpha)
-                                .with_dst_color_blendfactor(BlendFactor::OneMinusSrcAlpha)
-                                .with_src_alpha_blendfactor(BlendFactor::One)
-                                .with_dst_alpha_blendfactor(BlendFactor::OneMinusSrcAlpha)
-                                .with_alpha_blend_op(sdl3::gpu::BlendOp::Add)
-                                .with_color_blend_op(sdl3::gpu::BlendOp::Add)
-                                .with_enable_blend(true),
-                        )
-                        .with_format(target_texture_format),
-                ]),
-            )
-            .with_primitive_type(PrimitiveType::TriangleList)
-            .with_vertex_input_state(
-                VertexInputState::new()
-                    .with_vertex_buffer_descriptions(&[VertexBufferDescription::new()
-                        .with_slot(0)
-                        .with_input_rate(VertexInputRate::Vertex)
-                        .with_pitch(size_of::<Vertex>() as u32)
-                        .with_instance_step_rate(0)])
-                    .with_vertex_attributes(&[
-                        VertexAttribute::new()
-                            .with_buffer_slot(0)
-                            .with_offset(0)
-                            .with_location(0)
-                            .with_format(VertexElementFormat::Float3), // position
-                        VertexAttribute::new()
-                            .with_buffer_slot(0)
-                            .with_offset(size_of::<f32>() as u32 * 3)
-                            .with_location(1)
-                            .with_format(VertexElementFormat::Ubyte4Norm), // color
-                        VertexAttribute::new()
-                            .with_buffer_slot(0)
-                            .with_offset(
-                                (size_of::<f32>() as u32 * 3) + (size_of::<u8>() as u32 * 4),
-                            )
-                            .with_location(2)
-                            .with_format(VertexElementFormat::Float2), // texture_uv
-                        VertexAttribute::new()
-                            .with_buffer_slot(0)
-                            .with_offset(
-                                (size_of::<f32>() as u32 * 5) + (size_of::<u8>() as u32 * 4),
-                            )
-                            .with_location(3)
-                            .with_format(VertexElementFormat::Ubyte4Norm), // color
-                    ]),
-            )
-            .with_vertex_shader(&vs)
-            .with_fragment_shader(&fs)
-            .build()
-            .unwrap();
+        let graphics_pipeline = create_pipeline(&device, &vs, &fs, target_texture_format, &state)
+            .expect("Unable to create graphics pipeline");
 
         return Material {
             name,
-            pipeline: graphics_pipeline,
+            pipeline: Some(Rc::new(RefCell::new(graphics_pipeline))),
+            shader_watch: None,
             target_texture_format: target_texture_format,
+            state,
+            vertex_uniforms: Vec::new(),
+            fragment_uniforms: Vec::new(),
+            texture_slots: &[],
+            textures: Vec::new(),
         };
     }
 
+    /**
+     * Panics if `specification.source` declares resources we can't read or its vertex input
+     * doesn't match Vertex.
+     */
     pub fn from_specification(device: Device, specification: &MaterialSpecification) -> Self {
-        let supported_formats_bitflag = device.get_shader_formats();
-        let shader_format;
-        if (supported_formats_bitflag & ShaderFormat::MSL) == ShaderFormat::MSL {
-            shader_format = ShaderFormat::MSL
-        } else {
-            panic!("Shader format not supported")
-        }
-
-        let fs: Shader = device
-            .create_shader()
-            .with_samplers(specification.fragment.sampler_count) // Texture
-            .with_uniform_buffers(specification.fragment.uniform_buffer_count)
-            .with_code(
-                shader_format,
-                specification.fragment.src.as_bytes(),
-                ShaderStage::Fragment,
-            )
-            .with_entrypoint(FS_ENTRY)
-            .build()
-            .expect("Unable to create fragment shader");
-
-        let vs: Shader = device
-            .create_shader()
-            .with_samplers(specification.vertex.sampler_count)
-            .with_uniform_buffers(specification.vertex.uniform_buffer_count) // Projection Matrix
-            .with_code(
-                shader_format,
-                specification.vertex.src.as_bytes(),
-                ShaderStage::Vertex,
-            )
-            .with_entrypoint(VS_ENTRY)
-            .build()
-            .expect("Unable to create vertex shader");
+        let (vertex, fragment) = match specification.source {
+            Some(source) => reflect(source, specification.vertex, specification.fragment)
+                .unwrap_or_else(|e| panic!("Material {}: {}", specification.name, e)),
+            None => (specification.vertex, specification.fragment),
+        };
+        assert!(
+            specification.texture_slots.is_empty()
+                || specification.texture_slots.len() < fragment.sampler_count as usize,
+            "Material {} declares more texture slots than fragment samplers",
+            specification.name
+        );
+        let Device::Gpu(device) = device else {
+            return Self::headless_with_state(specification.name, specification.pipeline)
+                .with_texture_slots(specification.texture_slots);
+        };
+        let fs = fragment.create(&device, specification.name, ShaderStage::Fragment);
+        let vs = vertex.create(&device, specification.name, ShaderStage::Vertex);
 
-        return Self::new(
+        let mut material = Self::new(
             specification.name,
             device,
             vs,
             fs,
             specification.texture_format,
-        );
+            specification.pipeline,
+        )
+        .with_texture_slots(specification.texture_slots);
+        if let Some(hot_reload) = specification.hot_reload {
+            material = material.with_shader_watch(hot_reload, vertex, fragment);
+        }
+        return material;
     }
 }
 
+/**
+ * `vertex` and `fragment` with the resource counts `source` declares (see ShaderReflection),
+ * fails if its vertex input doesn't match Vertex.
+ */
+pub(crate) fn reflect(
+    source: &str,
+    vertex: ShaderSpecification,
+    fragment: ShaderSpecification,
+) -> Result<(ShaderSpecification, ShaderSpecification), String> {
+    let vertex_reflection = ShaderReflection::from_hlsl(source, ShaderStage::Vertex)?;
+    let fragment_reflection = ShaderReflection::from_hlsl(source, ShaderStage::Fragment)?;
+    let attributes = VERTEX_ATTRIBUTES.map(|(location, _, format)| (location, format));
+    vertex_reflection.validate_vertex_inputs(&attributes)?;
+    return Ok((
+        vertex.with_reflection(&vertex_reflection),
+        fragment.with_reflection(&fragment_reflection),
+    ));
+}
+
+fn create_pipeline(
+    device: &sdl3::gpu::Device,
+    vs: &Shader,
+    fs: &Shader,
+    target_texture_format: TextureFormat,
+    state: &PipelineSpecification,
+) -> Result<GraphicsPipeline, sdl3::Error> {
+    // The builder keeps pointers to these until build()
+    let color_targets = [ColorTargetDescription::new()
+        .with_blend_state(state.blend_mode.blend_state())
+        .with_format(target_texture_format)];
+    let mut target_info =
+        GraphicsPipelineTargetInfo::new().with_color_target_descriptions(&color_targets);
+    let mut depth_stencil_state = DepthStencilState::new();
+    if let Some(depth_stencil) = &state.depth_stencil {
+        target_info = target_info
+            .with_has_depth_stencil_target(true)
+            .with_depth_stencil_format(depth_stencil.format);
+        depth_stencil_state = depth_stencil.depth_stencil_state();
+    }
+
+    let vertex_buffers = [VertexBufferDescription::new()
+        .with_slot(0)
+        .with_input_rate(VertexInputRate::Vertex)
+        .with_pitch(size_of::<Vertex>() as u32)
+        .with_instance_step_rate(0)];
+    let vertex_attributes = VERTEX_ATTRIBUTES.map(|(location, offset, format)| {
+        VertexAttribute::new()
+            .with_buffer_slot(0)
+            .with_offset(offset as u32)
+            .with_location(location)
+            .with_format(format)
+    });
+    let builder = device
+        .create_graphics_pipeline()
+        .with_target_info(target_info)
+        .with_primitive_type(state.primitive_type)
+        .with_rasterizer_state(
+            RasterizerState::new()
+                .with_cull_mode(state.cull_mode)
+                .with_front_face(state.front_face),
+        )
+        .with_depth_stencil_state(depth_stencil_state)
+        .with_vertex_input_state(
+            VertexInputState::new()
+                .with_vertex_buffer_descriptions(&vertex_buffers)
+                .with_vertex_attributes(&vertex_attributes),
+        )
+        .with_vertex_shader(vs)
+        .with_fragment_shader(fs);
+    return with_sample_count(builder, state.sample_count).build();
+}
+
+/**
+ * sdl3's GraphicsPipelineBuilder has no setter for the multisample state. It is #[repr(C)],
+ * so a struct with the same layout reaches SDL_GPUGraphicsPipelineCreateInfo directly.
+ * Its fields are private and may change in any release: sdl3 is pinned to the version
+ * BuilderLayout was copied from (sdl3 0.15.1, src/sdl3/gpu/pipeline.rs), check it when bumping.
+ */
+fn with_sample_count(
+    builder: GraphicsPipelineBuilder<'_>,
+    sample_count: SampleCount,
+) -> GraphicsPipelineBuilder<'_> {
+    #[repr(C)]
+    struct BuilderLayout<'a> {
+        device: &'a sdl3::gpu::Device,
+        inner: SDL_GPUGraphicsPipelineCreateInfo,
+    }
+    const _: () = assert!(
+        size_of::<GraphicsPipelineBuilder>() == size_of::<BuilderLayout>()
+            && align_of::<GraphicsPipelineBuilder>() == align_of::<BuilderLayout>()
+    );
+    unsafe {
+        let mut layout: BuilderLayout = std::mem::transmute(builder);
+        layout.inner.multisample_state.sample_count = SDL_GPUSampleCount(sample_count as i32);
+        return std::mem::transmute::<BuilderLayout, GraphicsPipelineBuilder>(layout);
+    }
+}
+
+/**
+ * One shader stage, compiled ahead of time to every format we ship (see shaders/compile.sh).
+ * Formats left None are unavailable on the matching backend: SPIR-V for Vulkan,
+ * DXIL for Direct3D 12, MSL for Metal.
+ */
+#[derive(Clone, Copy)]
 pub struct ShaderSpecification {
-    pub src: &'static str,
+    pub msl: Option<&'static str>,
+    pub spirv: Option<&'static [u8]>,
+    pub dxil: Option<&'static [u8]>,
+    // Read from the HLSL instead when MaterialSpecification::source is set
     pub uniform_buffer_count: u32,
     pub sampler_count: u32,
 }
 
+impl ShaderSpecification {
+    /**
+     * The first of our formats the device accepts, with its code.
+     */
+    pub fn code(&self, supported_formats: ShaderFormat) -> Option<(ShaderFormat, &'static [u8])> {
+        let sources = [
+            (ShaderFormat::SPIRV, self.spirv),
+            (ShaderFormat::DXIL, self.dxil),
+            (ShaderFormat::MSL, self.msl.map(str::as_bytes)),
+        ];
+        return sources.into_iter().find_map(|(format, code)| {
+            let code = code?;
+            ((supported_formats & format) == format).then_some((format, code))
+        });
+    }
+
+    fn with_reflection(&self, reflection: &ShaderReflection) -> Self {
+        return ShaderSpecification {
+            uniform_buffer_count: reflection.uniform_buffer_count,
+            sampler_count: reflection.sampler_count,
+            ..*self
+        };
+    }
+
+    /**
+     * Panics if none of the formats this shader was compiled to runs on `device`.
+     */
+    fn create(&self, device: &sdl3::gpu::Device, material: &str, stage: ShaderStage) -> Shader {
+        let supported_formats = device.get_shader_formats();
+        let Some((format, code)) = self.code(supported_formats) else {
+            panic!(
+                "Material {}: no {:?} shader for the device's formats ({:?})",
+                material, stage, supported_formats
+            )
+        };
+        return self
+            .create_with_code(device, format, code, stage)
+            .expect("Unable to create shader");
+    }
+
+    /**
+     * `code` replaces the compiled sources, the resource counts still come from this specification.
+     */
+    pub(crate) fn create_with_code(
+        &self,
+        device: &sdl3::gpu::Device,
+        format: ShaderFormat,
+        code: &[u8],
+        stage: ShaderStage,
+    ) -> Result<Shader, sdl3::Error> {
+        let entrypoint = match stage {
+            ShaderStage::Fragment => FS_ENTRY,
+            _ => VS_ENTRY,
+        };
+        return device
+            .create_shader()
+            .with_samplers(self.sampler_count)
+            .with_uniform_buffers(self.uniform_buffer_count)
+            .with_code(format, code, stage)
+            .with_entrypoint(entrypoint)
+            .build();
+    }
+}
+
 pub struct MaterialSpecification {
     pub name: &'static str,
     pub fragment: ShaderSpecification,
     pub vertex: ShaderSpecification,
     pub texture_format: TextureFormat,
+    pub pipeline: PipelineSpecification,
+    // Names of the fragment samplers after the draw's texture (slot 0), in slot order
+    pub texture_slots: &'static [&'static str],
+    // HLSL the shaders were compiled from (include_str!). When set, the sampler and uniform
+    // counts of `vertex` and `fragment` are read from it and its VsInput is checked against Vertex
+    pub source: Option<&'static str>,
+    // Rebuild the shaders from HLSL at runtime when the file changes. None in shipped builds:
+    // `if cfg!(debug_assertions) { Some(..) } else { None }`
+    pub hot_reload: Option<ShaderHotReload>,
+}
+
+/**
+ * Fixed-function state baked into a Material's GraphicsPipeline. `depth_stencil` and
+ * `sample_count` describe the RenderTargets it can draw into.
+ * Start from PipelineSpecification::DEFAULT and override what differs:
+ *
+ *   pipeline: PipelineSpecification {
+ *       blend_mode: BlendMode::Additive,
+ *       ..PipelineSpecification::DEFAULT
+ *   },
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PipelineSpecification {
+    pub blend_mode: BlendMode,
+    // Batch::line adapts to LineList materials, every other Batch call emits triangles
+    pub primitive_type: PrimitiveType,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    // None: the pipeline renders to targets without a depth-stencil texture
+    pub depth_stencil: Option<DepthStencilSpecification>,
+    // Must match the sample count of the targets it renders to, see RenderTarget::with_msaa
+    pub sample_count: SampleCount,
+}
+
+impl PipelineSpecification {
+    // Alpha blended triangles, nothing culled, no depth-stencil
+    pub const DEFAULT: PipelineSpecification = PipelineSpecification {
+        blend_mode: BlendMode::Alpha,
+        primitive_type: PrimitiveType::TriangleList,
+        cull_mode: CullMode::None,
+        front_face: FrontFace::CounterClockwise,
+        depth_stencil: None,
+        sample_count: SampleCount::NoMultiSampling,
+    };
+}
+
+impl Default for PipelineSpecification {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/**
+ * How a fragment combines with what the target already holds.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    // src * src.a + dst * (1 - src.a)
+    #[default]
+    Alpha,
+    // src * src.a + dst, for particles and light accumulation
+    Additive,
+    // src * dst, for shadows and tinting
+    Multiply,
+    // src + dst * (1 - src.a), for colors already multiplied by their alpha
+    Premultiplied,
+    // src, alpha is written but not blended
+    Opaque,
+}
+
+impl BlendMode {
+    /**
+     * (src color, dst color, src alpha, dst alpha) factors, all combined with BlendOp::Add.
+     */
+    pub fn factors(self) -> [BlendFactor; 4] {
+        use BlendFactor::*;
+        return match self {
+            BlendMode::Alpha => [SrcAlpha, OneMinusSrcAlpha, One, OneMinusSrcAlpha],
+            BlendMode::Additive => [SrcAlpha, One, Zero, One],
+            BlendMode::Multiply => [DstColor, Zero, Zero, One],
+            BlendMode::Premultiplied => [One, OneMinusSrcAlpha, One, OneMinusSrcAlpha],
+            BlendMode::Opaque => [One, Zero, One, Zero],
+        };
+    }
+
+    fn blend_state(self) -> ColorTargetBlendState {
+        let [src_color, dst_color, src_alpha, dst_alpha] = self.factors();
+        return ColorTargetBlendState::new()
+            .with_src_color_blendfactor(src_color)
+            .with_dst_color_blendfactor(dst_color)
+            .with_src_alpha_blendfactor(src_alpha)
+            .with_dst_alpha_blendfactor(dst_alpha)
+            .with_alpha_blend_op(BlendOp::Add)
+            .with_color_blend_op(BlendOp::Add)
+            .with_enable_blend(self != BlendMode::Opaque);
+    }
+}
+
+/**
+ * Depth and stencil tests of a pipeline. The target it renders to needs a depth-stencil
+ * texture of `format`.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthStencilSpecification {
+    pub format: TextureFormat,
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_compare: CompareOp,
+    // None disables the stencil test
+    pub stencil: Option<StencilSpecification>,
+}
+
+/**
+ * Same for front and back faces. The reference value is set on the render pass, not here.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StencilSpecification {
+    pub compare: CompareOp,
+    pub pass: StencilOp,
+    pub fail: StencilOp,
+    pub depth_fail: StencilOp,
+    pub compare_mask: u8,
+    pub write_mask: u8,
+}
+
+impl DepthStencilSpecification {
+    fn depth_stencil_state(&self) -> DepthStencilState {
+        let mut state = DepthStencilState::new()
+            .with_enable_depth_test(self.depth_test)
+            .with_enable_depth_write(self.depth_write)
+            .with_compare_op(self.depth_compare);
+        if let Some(stencil) = &self.stencil {
+            let faces = || {
+                StencilOpState::new()
+                    .compare_op(stencil.compare)
+                    .with_pass_op(stencil.pass)
+                    .with_fail_op(stencil.fail)
+                    .with_depth_fail_op(stencil.depth_fail)
+            };
+            state = state
+                .with_enable_stencil_test(true)
+                .with_front_stencil_state(faces())
+                .with_back_stencil_state(faces())
+                .with_compare_mask(stencil.compare_mask)
+                .with_write_mask(stencil.write_mask);
+        }
+        return state;
+    }
 }
@@ -1,57 +1,99 @@
 use sdl3::gpu::{
-    Buffer, BufferRegion, BufferUsageFlags, CopyPass, Device, TransferBuffer, TransferBufferLocation, TransferBufferUsage
+    Buffer, BufferRegion, BufferUsageFlags, CopyPass, Device, TransferBuffer,
+    TransferBufferLocation, TransferBufferUsage,
 };
 
-use crate::graphics::{Vertex, MAX_INDICES, MAX_VERTICES};
+use crate::graphics::{MAX_INDICES, MAX_VERTICES, Vertex};
 
+/**
+ * GPU vertex + index buffers (and their transfer buffers) for one Batch.
+ * Starts at MAX_VERTICES / MAX_INDICES and grows, never shrinks, when a flush needs more.
+ * Growth shows up in BatchStats (vertex_capacity, index_capacity).
+ */
 pub struct Mesh {
     device: Device,
     pub vertex_buffer: Buffer,
     vertex_transfer_buffer: TransferBuffer,
     pub index_buffer: Buffer,
     index_transfer_buffer: TransferBuffer,
+    vertex_capacity: u32,
+    index_capacity: u32,
+    // Used part of the buffers, only that much gets uploaded
+    vertex_count: u32,
+    index_count: u32,
 }
 
 impl Mesh {
     pub fn new(device: Device) -> Self {
-        let vertex_buffer = device
-            .create_buffer()
-            .with_usage(BufferUsageFlags::VERTEX)
-            .with_size(size_of::<Vertex>() as u32 * MAX_VERTICES)
-            .build()
-            .unwrap();
+        let (vertex_buffer, vertex_transfer_buffer) =
+            Self::create_buffers::<Vertex>(&device, BufferUsageFlags::VERTEX, MAX_VERTICES);
+        let (index_buffer, index_transfer_buffer) =
+            Self::create_buffers::<u32>(&device, BufferUsageFlags::INDEX, MAX_INDICES);
 
-        let vertex_transfer_buffer = device
-            .create_transfer_buffer()
-            .with_usage(TransferBufferUsage::UPLOAD)
-            .with_size(size_of::<Vertex>() as u32 * MAX_VERTICES)
-            .build()
-            .unwrap();
+        return Mesh {
+            device,
+            vertex_buffer,
+            vertex_transfer_buffer,
+            index_buffer,
+            index_transfer_buffer,
+            vertex_capacity: MAX_VERTICES,
+            index_capacity: MAX_INDICES,
+            vertex_count: 0,
+            index_count: 0,
+        };
+    }
 
-        let index_buffer = device
+    fn create_buffers<T>(
+        device: &Device,
+        usage: BufferUsageFlags,
+        capacity: u32,
+    ) -> (Buffer, TransferBuffer) {
+        let size = size_of::<T>() as u32 * capacity;
+        let buffer = device
             .create_buffer()
-            .with_usage(BufferUsageFlags::INDEX)
-            .with_size(size_of::<u32>() as u32 * MAX_INDICES)
+            .with_usage(usage)
+            .with_size(size)
             .build()
             .unwrap();
 
-        let index_transfer_buffer = device
+        let transfer_buffer = device
             .create_transfer_buffer()
             .with_usage(TransferBufferUsage::UPLOAD)
-            .with_size(size_of::<u32>() as u32 * MAX_INDICES)
+            .with_size(size)
             .build()
             .unwrap();
 
-        return Mesh {
-            device,
-            vertex_buffer,
-            vertex_transfer_buffer,
-            index_buffer,
-            index_transfer_buffer,
-        };
+        return (buffer, transfer_buffer);
+    }
+
+    // Doubles until `needed` fits. Replaced buffers are released by SDL once the GPU is done with them.
+    fn grow(capacity: u32, needed: usize) -> u32 {
+        let mut capacity = capacity as usize;
+        while capacity < needed {
+            capacity *= 2;
+        }
+        return u32::try_from(capacity).expect("Mesh too large for a single upload");
+    }
+
+    pub fn vertex_capacity(&self) -> u32 {
+        self.vertex_capacity
+    }
+
+    pub fn index_capacity(&self) -> u32 {
+        self.index_capacity
     }
 
     pub fn set_data(&mut self, vertices: &[Vertex]) {
+        if vertices.len() > self.vertex_capacity as usize {
+            self.vertex_capacity = Self::grow(self.vertex_capacity, vertices.len());
+            (self.vertex_buffer, self.vertex_transfer_buffer) = Self::create_buffers::<Vertex>(
+                &self.device,
+                BufferUsageFlags::VERTEX,
+                self.vertex_capacity,
+            );
+        }
+        self.vertex_count = vertices.len() as u32;
+
         let mut map = self
             .vertex_transfer_buffer
             .map::<Vertex>(&self.device, true);
@@ -62,13 +104,27 @@ impl Mesh {
     }
 
     pub fn set_indices(&mut self, indices: &[u32]) {
+        if indices.len() > self.index_capacity as usize {
+            self.index_capacity = Self::grow(self.index_capacity, indices.len());
+            (self.index_buffer, self.index_transfer_buffer) = Self::create_buffers::<u32>(
+                &self.device,
+                BufferUsageFlags::INDEX,
+                self.index_capacity,
+            );
+        }
+        self.index_count = indices.len() as u32;
+
         let mut map = self.index_transfer_buffer.map::<u32>(&self.device, true);
         let memory = map.mem_mut();
         memory[..indices.len()].copy_from_slice(&indices);
         map.unmap();
     }
 
-    pub fn upload(&mut self, copy_pass : &CopyPass) {
+    pub fn upload(&mut self, copy_pass: &CopyPass) {
+        // Empty regions are invalid uploads, and there's nothing to draw anyway
+        if self.vertex_count == 0 || self.index_count == 0 {
+            return;
+        }
 
         // Upload vertices
         copy_pass.upload_to_gpu_buffer(
@@ -77,7 +133,7 @@ impl Mesh {
                 .with_offset(0),
             BufferRegion::new()
                 .with_buffer(&self.vertex_buffer)
-                .with_size(self.vertex_buffer.len()),
+                .with_size(size_of::<Vertex>() as u32 * self.vertex_count),
             false,
         );
         // Upload indices
@@ -87,9 +143,8 @@ impl Mesh {
                 .with_offset(0),
             BufferRegion::new()
                 .with_buffer(&self.index_buffer)
-                .with_size(self.index_buffer.len()),
+                .with_size(size_of::<u32>() as u32 * self.index_count),
             false,
         );
-
     }
 }
@@ -1,7 +1,14 @@
 pub mod batch;
+pub mod device;
 pub mod material;
 pub mod mesh;
+pub mod pixel_format;
+pub mod post_process;
+pub mod rasterizer;
+pub mod recording;
 pub mod render_target;
+pub mod shader_reflection;
+pub mod shader_reload;
 pub mod subtexture;
 pub mod texture;
 
@@ -16,6 +23,7 @@ pub struct Vertex {
     pub mult_wash_fill: [u8; 4],
 }
 
+// Initial size of a Batch's GPU buffers, they grow when a single draw_into needs more
 pub const MAX_VERTICES: u32 = 65536;
 pub const MAX_INDICES: u32 = MAX_VERTICES * 3 / 2;
 pub static IDENTITY: glm::Mat4 = glm::Mat4::new(
@@ -0,0 +1,272 @@
+use sdl3::gpu::TextureFormat;
+
+/**
+ * Decoded image as stb_image returns it: `channels` (1 gray, 2 gray + alpha, 3 RGB, 4 RGBA)
+ * interleaved, row-major.
+ */
+pub(crate) enum ImagePixels {
+    U8(Vec<u8>),
+    // HDR images (.hdr), linear
+    F32(Vec<f32>),
+}
+
+pub(crate) struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    pub pixels: ImagePixels,
+}
+
+/**
+ * Bytes per pixel of the formats Image::convert writes, None for the rest.
+ */
+pub(crate) fn bytes_per_pixel(format: TextureFormat) -> Option<u32> {
+    match format {
+        TextureFormat::R8Unorm => Some(1),
+        TextureFormat::R8g8b8a8Unorm
+        | TextureFormat::R8g8b8a8UnormSrgb
+        | TextureFormat::B8g8r8a8Unorm
+        | TextureFormat::B8g8r8a8UnormSrgb => Some(4),
+        TextureFormat::R16g16b16a16Float => Some(8),
+        TextureFormat::R32g32b32a32Float => Some(16),
+        _ => None,
+    }
+}
+
+impl Image {
+    /**
+     * Pixels in `format`, ready to be uploaded.
+     *
+     * 8-bit images are copied as they are: an *Srgb format only tells the GPU to decode them
+     * when sampling, float formats get the same values over 255.
+     * HDR images are linear: clamped to 0..1 for the 8-bit formats, and encoded for the
+     * *Srgb ones.
+     * R8Unorm keeps the first channel (gray, or red).
+     */
+    pub fn convert(&self, format: TextureFormat) -> Option<Vec<u8>> {
+        let bytes_per_pixel = bytes_per_pixel(format)?;
+        let srgb = matches!(
+            format,
+            TextureFormat::R8g8b8a8UnormSrgb | TextureFormat::B8g8r8a8UnormSrgb
+        );
+        let bgra = matches!(
+            format,
+            TextureFormat::B8g8r8a8Unorm | TextureFormat::B8g8r8a8UnormSrgb
+        );
+
+        let pixel_count = (self.width * self.height) as usize;
+        let mut data = Vec::with_capacity(pixel_count * bytes_per_pixel as usize);
+        for index in 0..pixel_count {
+            let [r, g, b, a] = self.rgba(index);
+            match format {
+                TextureFormat::R8Unorm => data.push(r.to_u8(false)),
+                TextureFormat::R16g16b16a16Float => {
+                    for channel in [r, g, b, a] {
+                        data.extend_from_slice(&f16_bits(channel.to_f32()).to_le_bytes());
+                    }
+                }
+                TextureFormat::R32g32b32a32Float => {
+                    for channel in [r, g, b, a] {
+                        data.extend_from_slice(&channel.to_f32().to_le_bytes());
+                    }
+                }
+                _ if bgra => data.extend_from_slice(&[
+                    b.to_u8(srgb),
+                    g.to_u8(srgb),
+                    r.to_u8(srgb),
+                    a.to_u8(false),
+                ]),
+                _ => data.extend_from_slice(&[
+                    r.to_u8(srgb),
+                    g.to_u8(srgb),
+                    b.to_u8(srgb),
+                    a.to_u8(false),
+                ]),
+            }
+        }
+        return Some(data);
+    }
+
+    // Gray is spread over RGB, a missing alpha is opaque
+    fn rgba(&self, index: usize) -> [Channel; 4] {
+        let channels = self.channels as usize;
+        let channel = |c: usize| match &self.pixels {
+            ImagePixels::U8(pixels) => Channel::U8(pixels[index * channels + c]),
+            ImagePixels::F32(pixels) => Channel::F32(pixels[index * channels + c]),
+        };
+        let opaque = match self.pixels {
+            ImagePixels::U8(_) => Channel::U8(255),
+            ImagePixels::F32(_) => Channel::F32(1f32),
+        };
+        return match channels {
+            1 => [channel(0), channel(0), channel(0), opaque],
+            2 => [channel(0), channel(0), channel(0), channel(1)],
+            3 => [channel(0), channel(1), channel(2), opaque],
+            _ => [channel(0), channel(1), channel(2), channel(3)],
+        };
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Channel {
+    U8(u8),
+    F32(f32),
+}
+
+impl Channel {
+    fn to_u8(self, srgb: bool) -> u8 {
+        match self {
+            Channel::U8(value) => value,
+            Channel::F32(value) => {
+                let value = if srgb { linear_to_srgb(value) } else { value };
+                (value.clamp(0f32, 1f32) * 255f32).round() as u8
+            }
+        }
+    }
+
+    fn to_f32(self) -> f32 {
+        match self {
+            Channel::U8(value) => value as f32 / 255f32,
+            Channel::F32(value) => value,
+        }
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        return value * 12.92;
+    }
+    return 1.055 * value.powf(1f32 / 2.4) - 0.055;
+}
+
+// IEEE half precision, rounded to nearest (subnormals are truncated)
+fn f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity or NaN
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        return sign | (mantissa >> (14 - exponent)) as u16;
+    }
+    // A carry out of the mantissa bumps the exponent, up to infinity
+    let half = ((exponent as u32) << 10 | mantissa >> 13) + ((mantissa >> 12) & 1);
+    return sign | half as u16;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(channels: u32, pixels: ImagePixels) -> Image {
+        Image {
+            width: 1,
+            height: 1,
+            channels,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn converts_8_bit_images_to_every_layout() {
+        let rgb = image(3, ImagePixels::U8(vec![10, 20, 30]));
+        let gray_alpha = image(2, ImagePixels::U8(vec![40, 128]));
+        assert_eq!(
+            rgb.convert(TextureFormat::R8g8b8a8Unorm),
+            Some(vec![10, 20, 30, 255])
+        );
+        // Copied as they are, the GPU decodes them
+        assert_eq!(
+            rgb.convert(TextureFormat::R8g8b8a8UnormSrgb),
+            Some(vec![10, 20, 30, 255])
+        );
+        assert_eq!(
+            rgb.convert(TextureFormat::B8g8r8a8Unorm),
+            Some(vec![30, 20, 10, 255])
+        );
+        assert_eq!(rgb.convert(TextureFormat::R8Unorm), Some(vec![10]));
+        assert_eq!(
+            gray_alpha.convert(TextureFormat::R8g8b8a8Unorm),
+            Some(vec![40, 40, 40, 128])
+        );
+        assert_eq!(rgb.convert(TextureFormat::D16Unorm), None);
+    }
+
+    #[test]
+    fn converts_to_float_formats() {
+        let rgba = image(4, ImagePixels::U8(vec![0, 51, 255, 255]));
+        let half: Vec<u8> = [0u16, 0x3266, 0x3c00, 0x3c00]
+            .iter()
+            .flat_map(|half| half.to_le_bytes())
+            .collect();
+        let float: Vec<u8> = [0f32, 0.2, 1.0, 1.0]
+            .iter()
+            .flat_map(|float| float.to_le_bytes())
+            .collect();
+        assert_eq!(rgba.convert(TextureFormat::R16g16b16a16Float), Some(half));
+        assert_eq!(rgba.convert(TextureFormat::R32g32b32a32Float), Some(float));
+    }
+
+    #[test]
+    fn clamps_and_encodes_hdr_images() {
+        let hdr = image(3, ImagePixels::F32(vec![0.5, 2.0, -1.0]));
+        assert_eq!(
+            hdr.convert(TextureFormat::R8g8b8a8Unorm),
+            Some(vec![128, 255, 0, 255])
+        );
+        assert_eq!(
+            hdr.convert(TextureFormat::B8g8r8a8UnormSrgb),
+            Some(vec![0, 255, 188, 255])
+        );
+        // Float formats keep the values out of 0..1
+        let float: Vec<u8> = [0.5f32, 2.0, -1.0, 1.0]
+            .iter()
+            .flat_map(|float| float.to_le_bytes())
+            .collect();
+        assert_eq!(hdr.convert(TextureFormat::R32g32b32a32Float), Some(float));
+    }
+
+    #[test]
+    fn f16_bits_matches_ieee_half_precision() {
+        let cases = [
+            (0f32, 0x0000),
+            (-0f32, 0x8000),
+            (1f32, 0x3c00),
+            (-2f32, 0xc000),
+            (0.5, 0x3800),
+            (1f32 + 2f32.powi(-10), 0x3c01),
+            (1f32 / 3f32, 0x3555),
+            (65504f32, 0x7bff),
+            // Smallest normal, then subnormals
+            (2f32.powi(-14), 0x0400),
+            (2f32.powi(-15), 0x0200),
+            (2f32.powi(-24), 0x0001),
+            (2f32.powi(-26), 0x0000),
+        ];
+        for (value, half) in cases {
+            assert_eq!(f16_bits(value), half, "{}", value);
+        }
+    }
+
+    #[test]
+    fn f16_bits_overflows_to_infinity() {
+        assert_eq!(f16_bits(65520f32), 0x7c00); // Rounds up past the largest half
+        assert_eq!(f16_bits(1e6), 0x7c00);
+        assert_eq!(f16_bits(f32::INFINITY), 0x7c00);
+        assert_eq!(f16_bits(f32::NEG_INFINITY), 0xfc00);
+        assert_eq!(f16_bits(f32::NAN), 0x7e00);
+    }
+}
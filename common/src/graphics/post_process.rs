@@ -0,0 +1,307 @@
+use sdl3::{gpu::TextureFormat, rect::Rect};
+
+use crate::graphics::{
+    VEC_2_ZERO,
+    batch::Batch,
+    device::Device,
+    material::{BlendMode, Material, PipelineSpecification},
+    render_target::RenderTarget,
+    subtexture::Subtexture,
+    texture::Texture,
+};
+
+/**
+ * One full-screen pass. The built-in effects only need the default shaders and a BlendMode,
+ * so they run on every backend (and on the headless rasterizer).
+ */
+#[derive(Clone)]
+pub enum Effect {
+    // Darkens every other row, 0 (off) to 1 (black)
+    Scanlines { strength: f32 },
+    // Adds a blurred copy of the bright parts of the image, `radius` in pixels
+    Bloom { strength: f32, radius: u32 },
+    // Multiplies the image by `tint`
+    ColorGrade { tint: [u8; 4] },
+    // Adds `color` on top, its alpha is the strength: fade it out to end the flash
+    Flash { color: [u8; 4] },
+    // Any full-screen shader: draws the input texture (sampler 0) with this material
+    Material(Material),
+}
+
+impl Effect {
+    // Passes that would copy the image unchanged, e.g. a faded out Flash
+    fn is_noop(&self) -> bool {
+        match self {
+            Effect::Scanlines { strength } | Effect::Bloom { strength, .. } => *strength <= 0f32,
+            Effect::ColorGrade { tint } => *tint == [255; 4],
+            Effect::Flash { color } => color[3] == 0,
+            Effect::Material(_) => false,
+        }
+    }
+}
+
+pub struct PostProcessPass {
+    pub name: &'static str,
+    pub effect: Effect,
+    pub enabled: bool,
+}
+
+/**
+ * Ordered list of full-screen passes run over a source RenderTarget:
+ *
+ *   let mut post_process = PostProcess::new(device, 320, 180);
+ *   post_process.push("scanlines", Effect::Scanlines { strength: 0.3 });
+ *   ...
+ *   batch.draw_into(&game_target);
+ *   batch.clear();
+ *   let output = post_process.apply(batch, &game_target);
+ *   batch.texture(output, &VEC_2_ZERO); // Drawn into the screen by the caller
+ *
+ * Passes ping-pong between two targets the size of the source, the source itself is never written.
+ */
+pub struct PostProcess {
+    pub passes: Vec<PostProcessPass>,
+    targets: [RenderTarget; 2],
+    // Bright parts of the image, blurred into the output by Effect::Bloom
+    bloom_target: RenderTarget,
+    opaque: Material,
+    additive: Material,
+    multiply: Material,
+}
+
+impl PostProcess {
+    pub fn new(device: Device, width: u16, height: u16) -> Self {
+        let target = || {
+            RenderTarget::new(Texture::new(
+                device.clone(),
+                width,
+                height,
+                TextureFormat::R8g8b8a8Unorm,
+            ))
+        };
+        let material = |name: &'static str, blend_mode: BlendMode| {
+            Material::default_with_state(
+                device.clone(),
+                name,
+                TextureFormat::R8g8b8a8Unorm,
+                PipelineSpecification {
+                    blend_mode,
+                    ..PipelineSpecification::DEFAULT
+                },
+            )
+        };
+        return PostProcess {
+            passes: Vec::new(),
+            targets: [target(), target()],
+            bloom_target: target(),
+            opaque: material("post process opaque", BlendMode::Opaque),
+            additive: material("post process additive", BlendMode::Additive),
+            multiply: material("post process multiply", BlendMode::Multiply),
+        };
+    }
+
+    pub fn push(&mut self, name: &'static str, effect: Effect) {
+        self.passes.push(PostProcessPass {
+            name,
+            effect,
+            enabled: true,
+        });
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut PostProcessPass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    /**
+     * Runs the enabled passes in order and returns the texture holding the result
+     * (`source`'s own texture when none are enabled). Passes that wouldn't change the image
+     * (a Flash with alpha 0, a Bloom of strength 0...) are skipped.
+     * Each pass is a draw_into of its own: `batch` must be empty and is left empty.
+     */
+    pub fn apply(&self, batch: &mut Batch, source: &RenderTarget) -> Texture {
+        assert!(
+            source.width == self.targets[0].width && source.height == self.targets[0].height,
+            "PostProcess targets are {}x{}, the source is {}x{}",
+            self.targets[0].width,
+            self.targets[0].height,
+            source.width,
+            source.height
+        );
+
+        let mut input = source.color();
+        let mut output = 0;
+        for pass in self
+            .passes
+            .iter()
+            .filter(|pass| pass.enabled && !pass.effect.is_noop())
+        {
+            if let Effect::Bloom { .. } = pass.effect {
+                self.extract_bright(batch, &input);
+            }
+
+            let target = &self.targets[output];
+            // Custom materials replace the input, the built-in effects draw on top of a copy
+            if !matches!(pass.effect, Effect::Material(_)) {
+                batch.push_material(&self.opaque);
+                batch.texture(input.clone(), &VEC_2_ZERO);
+                batch.pop_material();
+            }
+            self.draw_effect(batch, &pass.effect, &input, target);
+            batch.draw_into(target);
+            batch.clear();
+
+            input = target.color();
+            output = 1 - output;
+        }
+        return input;
+    }
+
+    fn draw_effect(
+        &self,
+        batch: &mut Batch,
+        effect: &Effect,
+        input: &Texture,
+        target: &RenderTarget,
+    ) {
+        let width = target.width as f32;
+        let height = target.height as f32;
+        match effect {
+            Effect::Scanlines { strength } => {
+                let shade = (255f32 * (1f32 - strength.clamp(0f32, 1f32))) as u8;
+                batch.push_material(&self.multiply);
+                for y in (1..target.height).step_by(2) {
+                    batch.rect(
+                        [0f32, y as f32, 0f32],
+                        [width, 1f32],
+                        [shade, shade, shade, 255],
+                    );
+                }
+                batch.pop_material();
+            }
+            Effect::Bloom { strength, radius } => {
+                // Box blur: copies of the bright parts offset along both axes, adding up to `strength`
+                let radius = *radius as i32;
+                let copies = (4 * radius + 1) as f32;
+                let alpha = (255f32 * strength.clamp(0f32, 1f32) / copies) as u8;
+                let bright = Subtexture::new(
+                    self.bloom_target.color(),
+                    Rect::new(0, 0, target.width, target.height),
+                );
+                let color = [255, 255, 255, alpha];
+                batch.push_material(&self.additive);
+                for offset in -radius..=radius {
+                    batch.subtexture_color(bright.clone(), glm::vec2(offset as f32, 0f32), color);
+                    if offset != 0 {
+                        batch.subtexture_color(
+                            bright.clone(),
+                            glm::vec2(0f32, offset as f32),
+                            color,
+                        );
+                    }
+                }
+                batch.pop_material();
+            }
+            Effect::ColorGrade { tint } => {
+                batch.push_material(&self.multiply);
+                batch.rect([0f32, 0f32, 0f32], [width, height], *tint);
+                batch.pop_material();
+            }
+            Effect::Flash { color } => {
+                batch.push_material(&self.additive);
+                batch.rect([0f32, 0f32, 0f32], [width, height], *color);
+                batch.pop_material();
+            }
+            Effect::Material(material) => {
+                batch.push_material(material);
+                batch.texture(input.clone(), &VEC_2_ZERO);
+                batch.pop_material();
+            }
+        }
+    }
+
+    // Squaring the image keeps the bright parts and fades out the rest (0.5 -> 0.25, 1 -> 1)
+    fn extract_bright(&self, batch: &mut Batch, input: &Texture) {
+        batch.push_material(&self.opaque);
+        batch.texture(input.clone(), &VEC_2_ZERO);
+        batch.pop_material();
+        batch.push_material(&self.multiply);
+        batch.texture(input.clone(), &VEC_2_ZERO);
+        batch.pop_material();
+        batch.draw_into(&self.bloom_target);
+        batch.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post_process(effect: Effect) -> PostProcess {
+        let mut post_process = PostProcess::new(Device::Headless, 4, 2);
+        post_process.push("effect", effect);
+        return post_process;
+    }
+
+    fn source() -> RenderTarget {
+        let mut source = RenderTarget::new(Texture::new(
+            Device::Headless,
+            4,
+            2,
+            TextureFormat::R8g8b8a8Unorm,
+        ));
+        source.set_clear_color(sdl3::pixels::Color::RGBA(128, 64, 255, 255));
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        batch.draw_into(&source);
+        return source;
+    }
+
+    fn first_pixel(texture: &Texture) -> [u8; 4] {
+        return texture.pixels().unwrap()[..4].try_into().unwrap();
+    }
+
+    #[test]
+    fn skips_passes_that_would_not_change_the_image() {
+        let source = source();
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        for effect in [
+            Effect::Flash {
+                color: [255, 255, 255, 0],
+            },
+            Effect::Bloom {
+                strength: 0f32,
+                radius: 2,
+            },
+        ] {
+            let output = post_process(effect).apply(&mut batch, &source);
+            assert_eq!(output.id, source.color().id);
+        }
+        assert!(batch.take_recorded_passes().is_empty());
+    }
+
+    #[test]
+    fn flash_adds_its_color_by_its_alpha() {
+        let source = source();
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        let output = post_process(Effect::Flash {
+            color: [0, 128, 0, 128],
+        })
+        .apply(&mut batch, &source);
+        assert_eq!(first_pixel(&output), [128, 128, 255, 255]);
+    }
+
+    #[test]
+    fn bloom_squares_the_image_before_blurring_it() {
+        let source = source();
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        let post_process = post_process(Effect::Bloom {
+            strength: 1f32,
+            radius: 0,
+        });
+        let output = post_process.apply(&mut batch, &source);
+        assert_eq!(
+            first_pixel(&post_process.bloom_target.color()),
+            [64, 16, 255, 255]
+        );
+        assert_eq!(first_pixel(&output), [192, 80, 255, 255]);
+    }
+}
@@ -0,0 +1,480 @@
+use std::ops::Range;
+
+use sdl3::gpu::{BlendFactor, PrimitiveType, SamplerAddressMode};
+
+use crate::graphics::{
+    Vertex,
+    material::BlendMode,
+    recording::{RecordedDraw, RecordedPass},
+    render_target::LoadAction,
+};
+
+/**
+ * Software renderer for RecordedPass, used by the headless Batch to fill CPU render targets.
+ *
+ * It mirrors the GPU path closely enough for pixel-exact snapshots of pixel-art output:
+ * - the default shader (default.hlsl): mult * texture * color + wash * texture.a * color + fill * color
+ * - nearest sampling, with the texture's address modes (filters and mipmaps are ignored)
+ * - the material's BlendMode
+ * - pixel centers at +0.5 and the top-left fill rule, so quads sharing an edge never overlap
+ * - the pass' LoadAction (Load and DontCare both keep the previous pixels)
+ * - per-draw scissor rects
+ * - triangle lists, and 1px line lists (approximately: one pixel per step along the major axis)
+ *
+ * Every material is shaded like the default one, custom shaders (e.g. red.hlsl) are not emulated.
+ * Neither are culling, depth-stencil attachments and state, multisampling and material texture
+ * slots (only sampler 0 is sampled).
+ */
+pub fn rasterize(pass: &RecordedPass, pixels: &mut [u8]) {
+    let width = pass.target_width as usize;
+    let height = pass.target_height as usize;
+    assert_eq!(pixels.len(), width * height * 4, "Target size mismatch");
+
+    if pass.load_action == LoadAction::Clear {
+        let clear = [
+            pass.clear_color.r,
+            pass.clear_color.g,
+            pass.clear_color.b,
+            pass.clear_color.a,
+        ];
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&clear);
+        }
+    }
+
+    let mut target = Target {
+        pixels,
+        width,
+        height,
+        clip_x: 0..width,
+        clip_y: 0..height,
+        blend_mode: BlendMode::Alpha,
+    };
+    for draw in &pass.draws {
+        (target.clip_x, target.clip_y) = match draw.scissor {
+            Some(scissor) => (
+                clip_range(scissor.x, scissor.w, width),
+                clip_range(scissor.y, scissor.h, height),
+            ),
+            None => (0..width, 0..height),
+        };
+        target.blend_mode = draw.blend_mode;
+        draw_primitives(pass, draw, &mut target);
+    }
+}
+
+struct Target<'a> {
+    pixels: &'a mut [u8],
+    width: usize,
+    height: usize,
+    // Pixels the current draw may touch (its scissor)
+    clip_x: Range<usize>,
+    clip_y: Range<usize>,
+    blend_mode: BlendMode,
+}
+
+struct Sampler<'a> {
+    pixels: Option<&'a [u8]>,
+    width: usize,
+    height: usize,
+    address_mode: [SamplerAddressMode; 2],
+}
+
+impl Sampler<'_> {
+    fn sample(&self, uv: [f32; 2]) -> [f32; 4] {
+        let Some(pixels) = self.pixels else {
+            return [0f32; 4]; // Nothing bound
+        };
+        let x = address(uv[0], self.width, self.address_mode[0]);
+        let y = address(uv[1], self.height, self.address_mode[1]);
+        let texel: [u8; 4] = pixels[(y * self.width + x) * 4..][..4].try_into().unwrap();
+        return texel.map(|channel| channel as f32 / 255f32);
+    }
+}
+
+// Texel index along one axis
+fn address(uv: f32, size: usize, mode: SamplerAddressMode) -> usize {
+    let texel = (uv * size as f32).floor() as i64;
+    let size = size as i64;
+    let texel = match mode {
+        SamplerAddressMode::ClampToEdge => texel.clamp(0, size - 1),
+        SamplerAddressMode::Repeat => texel.rem_euclid(size),
+        SamplerAddressMode::MirroredRepeat => {
+            let texel = texel.rem_euclid(2 * size);
+            if texel < size {
+                texel
+            } else {
+                2 * size - 1 - texel
+            }
+        }
+    };
+    return texel as usize;
+}
+
+fn draw_primitives(pass: &RecordedPass, draw: &RecordedDraw, target: &mut Target) {
+    let texture = draw.texture.as_ref();
+    // By id: a handle with another sampler still shares the target's pixels
+    if texture.is_some() && texture.map(|t| t.id) == pass.target.as_ref().map(|t| t.id) {
+        eprintln!("Skipping draw: sampling from its own render target");
+        return;
+    }
+    let texture_pixels = texture.and_then(|texture| texture.pixels());
+    let sampler = Sampler {
+        pixels: texture_pixels.as_ref().map(|pixels| pixels.as_slice()),
+        width: texture.map_or(0, |texture| texture.width() as usize),
+        height: texture.map_or(0, |texture| texture.height() as usize),
+        address_mode: texture.map_or([SamplerAddressMode::ClampToEdge; 2], |texture| {
+            let description = texture.sampler_description();
+            [description.address_mode_u, description.address_mode_v]
+        }),
+    };
+
+    match draw.primitive_type {
+        PrimitiveType::TriangleList => {
+            for triangle in pass.draw_indices(draw).chunks_exact(3) {
+                let vertices = [0, 1, 2].map(|i| &pass.vertices[triangle[i] as usize]);
+                draw_triangle(&pass.projection, vertices, &sampler, target);
+            }
+        }
+        PrimitiveType::LineList => {
+            for line in pass.draw_indices(draw).chunks_exact(2) {
+                let vertices = [0, 1].map(|i| &pass.vertices[line[i] as usize]);
+                draw_line(&pass.projection, vertices, &sampler, target);
+            }
+        }
+        primitive_type => eprintln!("Skipping draw: {:?} is not supported", primitive_type),
+    }
+}
+
+fn to_screen(projection: &glm::Mat4, vertex: &Vertex, target: &Target) -> glm::Vec2 {
+    let clip = projection
+        * glm::vec4(
+            vertex.position[0],
+            vertex.position[1],
+            vertex.position[2],
+            1f32,
+        );
+    return glm::vec2(
+        (clip.x / clip.w + 1f32) * 0.5 * target.width as f32,
+        (1f32 - clip.y / clip.w) * 0.5 * target.height as f32,
+    );
+}
+
+fn draw_line(
+    projection: &glm::Mat4,
+    vertices: [&Vertex; 2],
+    sampler: &Sampler,
+    target: &mut Target,
+) {
+    let from = to_screen(projection, vertices[0], target);
+    let to = to_screen(projection, vertices[1], target);
+    let delta = to - from;
+    // The last pixel is left out, like GPUs do, so connected lines don't overlap
+    let steps = delta.x.abs().max(delta.y.abs()).round() as usize;
+    for step in 0..steps {
+        let t = (step as f32 + 0.5) / steps as f32;
+        let point = from + delta * t;
+        if point.x < 0f32 || point.y < 0f32 {
+            continue;
+        }
+        let (x, y) = (point.x as usize, point.y as usize);
+        if !target.clip_x.contains(&x) || !target.clip_y.contains(&y) {
+            continue;
+        }
+        let fragment = shade(
+            [vertices[0], vertices[1], vertices[0]],
+            [1f32 - t, t, 0f32],
+            sampler,
+        );
+        let blend_mode = target.blend_mode;
+        blend(
+            &mut target.pixels[(y * target.width + x) * 4..][..4],
+            fragment,
+            blend_mode,
+        );
+    }
+}
+
+fn draw_triangle(
+    projection: &glm::Mat4,
+    mut vertices: [&Vertex; 3],
+    sampler: &Sampler,
+    target: &mut Target,
+) {
+    let mut points = vertices.map(|vertex| to_screen(projection, vertex, target));
+
+    let mut area = edge(&points[0], &points[1], &points[2]);
+    if area == 0f32 {
+        return;
+    }
+    // No culling: wind every triangle the same way so a single fill rule applies
+    if area < 0f32 {
+        points.swap(1, 2);
+        vertices.swap(1, 2);
+        area = -area;
+    }
+
+    let min_x = points.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+    let max_x = points.iter().map(|p| p.x).fold(f32::MIN, f32::max);
+    let min_y = points.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+    let max_y = points.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+    let x_range = intersect(pixel_range(min_x, max_x, target.width), &target.clip_x);
+    let y_range = intersect(pixel_range(min_y, max_y, target.height), &target.clip_y);
+
+    let edges = [(1, 2), (2, 0), (0, 1)];
+    let top_left = edges.map(|(a, b)| is_top_left(&points[a], &points[b]));
+
+    for y in y_range {
+        for x in x_range.clone() {
+            let center = glm::vec2(x as f32 + 0.5, y as f32 + 0.5);
+            let weights = edges.map(|(a, b)| edge(&points[a], &points[b], &center));
+            let inside = (0..3).all(|i| weights[i] > 0f32 || (weights[i] == 0f32 && top_left[i]));
+            if !inside {
+                continue;
+            }
+            let weights = weights.map(|weight| weight / area);
+            let fragment = shade(vertices, weights, sampler);
+            blend(
+                &mut target.pixels[(y * target.width + x) * 4..][..4],
+                fragment,
+                target.blend_mode,
+            );
+        }
+    }
+}
+
+fn pixel_range(min: f32, max: f32, size: usize) -> Range<usize> {
+    let start = (min - 0.5).ceil().max(0f32) as usize;
+    let end = ((max - 0.5).floor() + 1f32).clamp(0f32, size as f32) as usize;
+    start.min(end)..end
+}
+
+fn clip_range(start: i32, length: i32, size: usize) -> Range<usize> {
+    let end = (start + length).clamp(0, size as i32) as usize;
+    let start = start.clamp(0, size as i32) as usize;
+    start.min(end)..end
+}
+
+fn intersect(range: Range<usize>, clip: &Range<usize>) -> Range<usize> {
+    let start = range.start.max(clip.start);
+    let end = range.end.min(clip.end);
+    start.min(end)..end
+}
+
+// Twice the signed area of (a, b, p), positive when clockwise on screen (y down)
+fn edge(a: &glm::Vec2, b: &glm::Vec2, p: &glm::Vec2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// For clockwise triangles: top edges go right, left edges go up
+fn is_top_left(a: &glm::Vec2, b: &glm::Vec2) -> bool {
+    (a.y == b.y && b.x > a.x) || b.y < a.y
+}
+
+fn shade(vertices: [&Vertex; 3], weights: [f32; 3], sampler: &Sampler) -> [f32; 4] {
+    let interpolate = |attribute: &dyn Fn(&Vertex) -> f32| {
+        (0..3)
+            .map(|i| attribute(vertices[i]) * weights[i])
+            .sum::<f32>()
+    };
+    let unorm = |value: u8| value as f32 / 255f32;
+
+    let uv = [
+        interpolate(&|v| v.texture_uv[0]),
+        interpolate(&|v| v.texture_uv[1]),
+    ];
+    let color: [f32; 4] = [0, 1, 2, 3].map(|c| interpolate(&|v| unorm(v.color[c])));
+    let mult = interpolate(&|v| unorm(v.mult_wash_fill[0]));
+    let wash = interpolate(&|v| unorm(v.mult_wash_fill[1]));
+    let fill = interpolate(&|v| unorm(v.mult_wash_fill[2]));
+
+    let texture = sampler.sample(uv);
+    return [0, 1, 2, 3]
+        .map(|c| mult * texture[c] * color[c] + wash * texture[3] * color[c] + fill * color[c]);
+}
+
+fn blend(destination: &mut [u8], source: [f32; 4], blend_mode: BlendMode) {
+    let source = source.map(|channel| channel.clamp(0f32, 1f32));
+    let target: [f32; 4] = [0, 1, 2, 3].map(|c| destination[c] as f32 / 255f32);
+    let [src_color, dst_color, src_alpha, dst_alpha] = blend_mode.factors();
+    for c in 0..4 {
+        let (src_factor, dst_factor) = if c == 3 {
+            (src_alpha, dst_alpha)
+        } else {
+            (src_color, dst_color)
+        };
+        let out = source[c] * factor(src_factor, &source, &target, c)
+            + target[c] * factor(dst_factor, &source, &target, c);
+        destination[c] = (out.clamp(0f32, 1f32) * 255f32).round() as u8;
+    }
+}
+
+fn factor(factor: BlendFactor, source: &[f32; 4], target: &[f32; 4], c: usize) -> f32 {
+    return match factor {
+        BlendFactor::Zero => 0f32,
+        BlendFactor::SrcColor => source[c],
+        BlendFactor::OneMinusSrcColor => 1f32 - source[c],
+        BlendFactor::DstColor => target[c],
+        BlendFactor::OneMinusDstColor => 1f32 - target[c],
+        BlendFactor::SrcAlpha => source[3],
+        BlendFactor::OneMinusSrcAlpha => 1f32 - source[3],
+        BlendFactor::DstAlpha => target[3],
+        BlendFactor::OneMinusDstAlpha => 1f32 - target[3],
+        _ => 1f32,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl3::{gpu::TextureFormat, pixels::Color, rect::Rect};
+
+    use super::*;
+    use crate::graphics::{
+        batch::Batch,
+        device::Device,
+        material::{Material, PipelineSpecification},
+        render_target::RenderTarget,
+        texture::Texture,
+    };
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    fn target(width: u16, height: u16) -> RenderTarget {
+        let mut target = RenderTarget::new(Texture::new(
+            Device::Headless,
+            width,
+            height,
+            TextureFormat::R8g8b8a8Unorm,
+        ));
+        target.set_clear_color(Color::RGBA(0, 0, 0, 255));
+        return target;
+    }
+
+    fn draw(target: &RenderTarget, load_action: LoadAction, draw: impl FnOnce(&mut Batch)) {
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        draw(&mut batch);
+        batch.draw_into_with(target, load_action);
+    }
+
+    fn pixel(target: &RenderTarget, x: usize, y: usize) -> [u8; 4] {
+        let pixels = target.color().pixels().unwrap().clone();
+        let index = (y * target.width as usize + x) * 4;
+        return pixels[index..index + 4].try_into().unwrap();
+    }
+
+    // One char per pixel: '#' for `color`, '.' for anything else
+    fn mask(target: &RenderTarget, color: [u8; 4]) -> Vec<String> {
+        (0..target.height as usize)
+            .map(|y| {
+                (0..target.width as usize)
+                    .map(|x| {
+                        if pixel(target, x, y) == color {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fills_exactly_the_pixels_a_rect_covers() {
+        let target = target(4, 4);
+        draw(&target, LoadAction::Clear, |batch| {
+            batch.rect([1f32, 1f32, 0f32], [2f32, 2f32], RED);
+        });
+        assert_eq!(mask(&target, RED), ["....", ".##.", ".##.", "...."]);
+        assert_eq!(pixel(&target, 0, 0), BLACK);
+    }
+
+    #[test]
+    fn quads_sharing_an_edge_never_overlap() {
+        let target = target(4, 2);
+        let additive = Material::default_with_state(
+            Device::Headless,
+            "additive",
+            TextureFormat::R8g8b8a8Unorm,
+            PipelineSpecification {
+                blend_mode: BlendMode::Additive,
+                ..PipelineSpecification::DEFAULT
+            },
+        );
+        let color = [100, 0, 0, 255];
+        draw(&target, LoadAction::Clear, |batch| {
+            batch.push_material(&additive);
+            batch.rect([0f32, 0f32, 0f32], [2f32, 2f32], color);
+            batch.rect([2f32, 0f32, 0f32], [2f32, 2f32], color);
+            batch.rect([0f32, 0f32, 0f32], [4f32, 1f32], color);
+            batch.pop_material();
+        });
+        // Added once by the side by side quads, twice where the top row overlaps them
+        assert_eq!(mask(&target, [200, 0, 0, 255]), ["####", "...."]);
+        assert_eq!(mask(&target, [100, 0, 0, 255]), ["....", "####"]);
+    }
+
+    #[test]
+    fn load_keeps_what_earlier_passes_drew() {
+        let target = target(2, 1);
+        draw(&target, LoadAction::Clear, |batch| {
+            batch.rect([0f32, 0f32, 0f32], [1f32, 1f32], RED);
+        });
+        draw(&target, LoadAction::Load, |batch| {
+            batch.rect([1f32, 0f32, 0f32], [1f32, 1f32], [0, 0, 255, 255]);
+        });
+        assert_eq!(pixel(&target, 0, 0), RED);
+        assert_eq!(pixel(&target, 1, 0), [0, 0, 255, 255]);
+
+        draw(&target, LoadAction::Clear, |_| {});
+        assert_eq!(mask(&target, BLACK), ["##"]);
+    }
+
+    #[test]
+    fn scissors_clip_draws() {
+        let target = target(4, 4);
+        draw(&target, LoadAction::Clear, |batch| {
+            batch.push_scissor(Rect::new(1, 2, 2, 1));
+            batch.rect([0f32, 0f32, 0f32], [4f32, 4f32], RED);
+            batch.pop_scissor();
+        });
+        assert_eq!(mask(&target, RED), ["....", "....", ".##.", "...."]);
+    }
+
+    #[test]
+    fn samples_the_nearest_texel() {
+        let texture = Texture::new(Device::Headless, 2, 2, TextureFormat::R8g8b8a8Unorm);
+        let texels = [
+            RED,
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [255, 255, 255, 255],
+        ];
+        texture.write_pixels(texels.as_flattened());
+
+        let target = target(4, 4);
+        draw(&target, LoadAction::Clear, |batch| {
+            batch.push_matrix(glm::scale(
+                &glm::Mat4::identity(),
+                &glm::vec3(2f32, 2f32, 1f32),
+            ));
+            batch.texture(texture.clone(), &glm::vec2(0f32, 0f32));
+            batch.pop_matrix();
+        });
+        for (index, texel) in texels.iter().enumerate() {
+            let (x, y) = (index % 2 * 2, index / 2 * 2);
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                assert_eq!(pixel(&target, x + dx, y + dy), *texel);
+            }
+        }
+    }
+
+    #[test]
+    fn address_modes_wrap_texels() {
+        let texels = |mode| [-0.25f32, 0.25, 1.25, 1.75].map(|uv| address(uv, 4, mode));
+        assert_eq!(texels(SamplerAddressMode::ClampToEdge), [0, 1, 3, 3]);
+        assert_eq!(texels(SamplerAddressMode::Repeat), [3, 1, 1, 3]);
+        assert_eq!(texels(SamplerAddressMode::MirroredRepeat), [0, 1, 2, 0]);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    fmt::{self, Display},
+    hash::Hasher,
+};
+
+use sdl3::{gpu::PrimitiveType, pixels::Color, rect::Rect};
+
+use crate::{
+    graphics::{Vertex, material::BlendMode, render_target::LoadAction, texture::Texture},
+    utils::Fnv1a,
+};
+
+/**
+ * One draw call of a RecordedPass. Ranges index into the pass' `indices` / `vertices`.
+ */
+#[derive(Clone)]
+pub struct RecordedDraw {
+    pub material: &'static str,
+    // The material's named texture slots (sampler 1..), `texture` is sampler 0
+    pub slot_textures: Vec<(&'static str, Option<Texture>)>,
+    pub blend_mode: BlendMode,
+    pub primitive_type: PrimitiveType,
+    pub texture: Option<Texture>,
+    // Target pixels, None when unclipped
+    pub scissor: Option<Rect>,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub first_vertex: u32,
+    pub vertex_count: u32,
+}
+
+/**
+ * Everything one Batch::draw_into call submits, captured on the CPU (see Batch::record).
+ * Vertices are already transformed by the batch's matrix stack, `projection` is the target's.
+ */
+#[derive(Clone)]
+pub struct RecordedPass {
+    // None when drawing to the screen
+    pub target: Option<Texture>,
+    pub target_width: u32,
+    pub target_height: u32,
+    pub clear_color: Color,
+    pub load_action: LoadAction,
+    pub projection: glm::Mat4,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub draws: Vec<RecordedDraw>,
+}
+
+impl RecordedPass {
+    pub fn draw_indices(&self, draw: &RecordedDraw) -> &[u32] {
+        let first = draw.first_index as usize;
+        &self.indices[first..first + draw.index_count as usize]
+    }
+
+    pub fn draw_vertices(&self, draw: &RecordedDraw) -> &[Vertex] {
+        let first = draw.first_vertex as usize;
+        &self.vertices[first..first + draw.vertex_count as usize]
+    }
+}
+
+/**
+ * Text form meant for golden files, one line per pass and one per draw:
+ *
+ *   pass target=3 320x180 projection [0.00625 0 0 0 ...]
+ *     draw material=default texture=2 indices=0+6 vertices=0+4 hash=6c62272e07bb0142
+ *     draw material=default texture=- indices=6+6 vertices=4+4 hash=af63bd4c8601b7df scissor=10,10,64x32
+ *
+ * `load` (on the pass), `blend`, `primitive` and `scissor` are only written when they differ
+ * from the default (cleared, alpha blended, triangle list, unclipped). Materials with texture slots add `<slot>=<id|->`.
+ * `hash` covers the draw's vertices and indices, so any change in geometry, uvs or colors
+ * shows up without dumping every vertex.
+ */
+impl Display for RecordedPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let target = match &self.target {
+            Some(texture) => texture.id.to_string(),
+            None => "screen".to_string(),
+        };
+        write!(
+            f,
+            "pass target={} {}x{} projection [",
+            target, self.target_width, self.target_height
+        )?;
+        for (i, value) in self.projection.iter().enumerate() {
+            let separator = if i == 0 { "" } else { " " };
+            write!(f, "{}{}", separator, value)?;
+        }
+        write!(f, "]")?;
+        if self.load_action != LoadAction::Clear {
+            write!(f, " load={:?}", self.load_action)?;
+        }
+        writeln!(f)?;
+
+        for draw in &self.draws {
+            let texture = match &draw.texture {
+                Some(texture) => texture.id.to_string(),
+                None => "-".to_string(),
+            };
+            write!(
+                f,
+                "  draw material={} texture={} indices={}+{} vertices={}+{} hash={:016x}",
+                draw.material,
+                texture,
+                draw.first_index,
+                draw.index_count,
+                draw.first_vertex,
+                draw.vertex_count,
+                self.draw_hash(draw)
+            )?;
+            for (name, texture) in &draw.slot_textures {
+                match texture {
+                    Some(texture) => write!(f, " {}={}", name, texture.id)?,
+                    None => write!(f, " {}=-", name)?,
+                }
+            }
+            if draw.blend_mode != BlendMode::Alpha {
+                write!(f, " blend={:?}", draw.blend_mode)?;
+            }
+            if draw.primitive_type != PrimitiveType::TriangleList {
+                write!(f, " primitive={:?}", draw.primitive_type)?;
+            }
+            if let Some(scissor) = draw.scissor {
+                write!(
+                    f,
+                    " scissor={},{},{}x{}",
+                    scissor.x, scissor.y, scissor.w, scissor.h
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl RecordedPass {
+    // FNV-1a over the little-endian bytes, indices relative to the draw's first vertex
+    fn draw_hash(&self, draw: &RecordedDraw) -> u64 {
+        let mut hasher = Fnv1a::default();
+        for vertex in self.draw_vertices(draw) {
+            for value in vertex.position {
+                hasher.write(&value.to_le_bytes());
+            }
+            hasher.write(&vertex.color);
+            for value in vertex.texture_uv {
+                hasher.write(&value.to_le_bytes());
+            }
+            hasher.write(&vertex.mult_wash_fill);
+        }
+        for index in self.draw_indices(draw) {
+            hasher.write(&(index - draw.first_vertex).to_le_bytes());
+        }
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl3::gpu::TextureFormat;
+
+    use super::*;
+    use crate::graphics::{
+        batch::Batch, device::Device, material::Material, render_target::RenderTarget,
+    };
+
+    fn record(draw: impl FnOnce(&mut Batch)) -> RecordedPass {
+        let mut batch = Batch::new(Device::Headless, Material::headless("default"));
+        draw(&mut batch);
+        let mut target = RenderTarget::empty();
+        target.resize(64, 32);
+        return batch.record(&target, LoadAction::Clear);
+    }
+
+    fn texture() -> Texture {
+        Texture::new(Device::Headless, 4, 4, TextureFormat::R8g8b8a8Unorm)
+    }
+
+    #[test]
+    fn writes_one_line_per_pass_and_draw() {
+        let (first, second) = (texture(), texture());
+        let pass = record(|batch| {
+            batch.texture(first.clone(), &glm::vec2(0f32, 0f32));
+            batch.texture(second.clone(), &glm::vec2(8f32, 0f32));
+        });
+
+        let text = pass.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines[0].starts_with("pass target=screen 64x32 projection [0.03125 0 0 0 0 -0.0625")
+        );
+        assert!(lines[1].starts_with(&format!(
+            "  draw material=default texture={} indices=0+6 vertices=0+4 hash=",
+            first.id
+        )));
+        assert!(lines[2].starts_with(&format!(
+            "  draw material=default texture={} indices=6+6 vertices=4+4 hash=",
+            second.id
+        )));
+    }
+
+    #[test]
+    fn only_writes_what_differs_from_the_defaults() {
+        let pass = record(|batch| {
+            batch.push_scissor(Rect::new(1, 2, 3, 4));
+            batch.rect([0f32, 0f32, 0f32], [8f32, 8f32], [255, 0, 0, 255]);
+            batch.pop_scissor();
+        });
+        let mut pass_with_load = pass.clone();
+        pass_with_load.load_action = LoadAction::Load;
+
+        let text = pass.to_string();
+        assert!(
+            !text.contains("load=") && !text.contains("blend=") && !text.contains("primitive=")
+        );
+        assert!(text.lines().nth(1).unwrap().ends_with(" scissor=1,2,3x4"));
+        assert!(
+            pass_with_load
+                .to_string()
+                .lines()
+                .next()
+                .unwrap()
+                .ends_with(" load=Load")
+        );
+    }
+
+    #[test]
+    fn hash_covers_the_geometry_not_where_it_sits_in_the_buffers() {
+        let (first, second) = (texture(), texture());
+        let pass = record(|batch| {
+            batch.texture(first.clone(), &glm::vec2(0f32, 0f32));
+            batch.texture(second.clone(), &glm::vec2(0f32, 0f32));
+        });
+        let moved = record(|batch| batch.texture(first.clone(), &glm::vec2(1f32, 0f32)));
+
+        let hashes: Vec<u64> = pass.draws.iter().map(|draw| pass.draw_hash(draw)).collect();
+        assert_eq!(hashes[0], hashes[1]);
+        assert_ne!(hashes[0], moved.draw_hash(&moved.draws[0]));
+    }
+}
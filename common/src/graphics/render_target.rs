@@ -1,14 +1,33 @@
 use std::mem;
 
 use sdl3::{
-    gpu::{ColorTargetInfo, LoadOp, StoreOp},
+    gpu::{
+        ColorTargetInfo, CommandBuffer, DepthStencilTargetInfo, LoadOp, SampleCount, StoreOp,
+        TextureFormat,
+    },
     pixels::Color,
+    rect::Rect,
+    sys::gpu::{SDL_GPUColorTargetInfo, SDL_GPUDepthStencilTargetInfo},
 };
 
-use crate::graphics::texture::Texture;
+use crate::graphics::{
+    device::Device,
+    texture::{Download, Texture},
+};
+
+// color_target_info and depth_stencil_target_info fill these sdl3 wrappers through the SDL
+// structs they hold. Their fields are private: sdl3 is pinned to the version they were checked
+// against (sdl3 0.15.1, src/sdl3/gpu/pass.rs), check it again when bumping.
+const _: () = assert!(
+    size_of::<ColorTargetInfo>() == size_of::<SDL_GPUColorTargetInfo>()
+        && align_of::<ColorTargetInfo>() == align_of::<SDL_GPUColorTargetInfo>()
+        && size_of::<DepthStencilTargetInfo>() == size_of::<SDL_GPUDepthStencilTargetInfo>()
+        && align_of::<DepthStencilTargetInfo>() == align_of::<SDL_GPUDepthStencilTargetInfo>()
+);
 
 enum BackingTexture {
-    Screen(Option<sdl3::gpu::Texture<'static>>),
+    // The swapchain texture of the current frame, and its format
+    Screen(Option<(sdl3::gpu::Texture<'static>, TextureFormat)>),
     Texture(Texture),
 }
 
@@ -25,11 +44,39 @@ const CLEAR_COLOR: Color = Color {
     a: 255,
 };
 
+/**
+ * What a render pass does with the target's previous contents.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoadAction {
+    // Fill the target with its clear color first
+    Clear,
+    // Keep what earlier passes drew, to compose several batches into one target
+    Load,
+    // Previous contents are undefined, cheapest when the pass overwrites every pixel
+    DontCare,
+}
+
 pub struct RenderTarget {
+    // What the projection maps to: logical (window) units for the screen, pixels otherwise
     pub width: u32,
     pub height: u32,
+    // Size of the texture passes draw into, see resize_pixels
+    pixel_width: u32,
+    pixel_height: u32,
+    // Used by Batch::draw_into, Batch::draw_into_with picks another one for a single pass
+    pub load_action: LoadAction,
+    clear_color: Color,
+    pub clear_depth: f32,
+    pub clear_stencil: u8,
+    // What StencilSpecification::compare tests against, for every draw into this target
+    pub stencil_reference: u8,
     projection: Option<glm::Mat4>,
     texture: BackingTexture,
+    // See with_depth_stencil
+    depth_stencil: Option<(Texture, TextureFormat)>,
+    // See with_msaa: passes draw into this texture, it's resolved into `texture` when they end
+    multisample: Option<(Texture, SampleCount)>,
 }
 
 impl RenderTarget {
@@ -38,10 +85,31 @@ impl RenderTarget {
             projection: None,
             width: 0,
             height: 0,
+            pixel_width: 0,
+            pixel_height: 0,
+            load_action: LoadAction::Clear,
+            clear_color: CLEAR_COLOR_SCREEN,
+            clear_depth: 1f32,
+            clear_stencil: 0,
+            stencil_reference: 0,
             texture: BackingTexture::Screen(None),
+            depth_stencil: None,
+            multisample: None,
         }
     }
 
+    pub fn is_screen(&self) -> bool {
+        matches!(self.texture, BackingTexture::Screen(_))
+    }
+
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    pub fn set_clear_color(&mut self, color: Color) {
+        self.clear_color = color;
+    }
+
     pub fn color(&self) -> Texture {
         if let BackingTexture::Texture(texture) = &self.texture {
             return texture.clone();
@@ -53,11 +121,12 @@ impl RenderTarget {
      * This API is only for the screen texture and should not be used
      * for regular render targets.
      */
-    pub fn set_texture(&mut self, texture: sdl3::gpu::Texture<'_>) {
+    pub fn set_texture(&mut self, texture: sdl3::gpu::Texture<'_>, format: TextureFormat) {
         // Only sets the projection matrix once for performance
         if self.projection.is_none() {
             self.resize(texture.width(), texture.height())
         }
+        self.resize_pixels(texture.width(), texture.height());
 
         // Unsafe:
         // RenderTarget lives longer (outside main_loop) than the swapchain_texture (inside main_loop)
@@ -69,15 +138,32 @@ impl RenderTarget {
         //   let render_target = RenderTarget::with_matrix(matrix); // 'static lifetime (long)
         //   loop {
         //       let cmd = ...
-        //       render_target.set_texture(cmd.swapchain_texture()); // 'loop lifetime (short)
+        //       render_target.set_texture(cmd.swapchain_texture(), format); // 'loop lifetime (short)
         //       batch.render(render_target);
         //       render_target.clear_texture(); // Clear before texture is dropped
         //   }
         unsafe {
-            self.texture = BackingTexture::Screen(Some(mem::transmute(texture)));
+            self.texture = BackingTexture::Screen(Some((mem::transmute(texture), format)));
         }
     }
 
+    /**
+     * Screen only: records a copy of the frame into `command_buffer`, the one the swapchain
+     * texture was acquired with. The swapchain texture is only valid until that command buffer
+     * is submitted (and presented), so read the Download once it finished, not before.
+     * Its size is in pixels (see resize_pixels). Offscreen targets use color().read_pixels().
+     */
+    pub fn record_download(
+        &self,
+        device: &sdl3::gpu::Device,
+        command_buffer: &CommandBuffer,
+    ) -> Download {
+        let BackingTexture::Screen(Some((texture, format))) = &self.texture else {
+            panic!("Only the screen's swapchain texture is downloaded with its frame")
+        };
+        return Download::record(device, command_buffer, texture, *format);
+    }
+
     /**
      * This API is only for the screen texture and should not be used
      * for regular render targets.
@@ -103,10 +189,77 @@ impl RenderTarget {
             projection: Some(projection),
             width: texture.width(),
             height: texture.height(),
+            pixel_width: texture.width(),
+            pixel_height: texture.height(),
+            load_action: LoadAction::Clear,
+            clear_color: CLEAR_COLOR,
+            clear_depth: 1f32,
+            clear_stencil: 0,
+            stencil_reference: 0,
             texture: BackingTexture::Texture(texture),
+            depth_stencil: None,
+            multisample: None,
         }
     }
 
+    /**
+     * Adds a depth-stencil attachment (e.g. TextureFormat::D24UnormS8Uint), cleared to
+     * clear_depth / clear_stencil or kept like the color following the pass' LoadAction.
+     * Materials drawn into this target need a PipelineSpecification::depth_stencil of the same format.
+     */
+    pub fn with_depth_stencil(mut self, device: Device, format: TextureFormat) -> Self {
+        // TODO: screen targets are resized with the window, their attachments would need to follow
+        assert!(
+            !self.is_screen(),
+            "Screen targets can't have attachments yet"
+        );
+        let texture =
+            Texture::attachment(device, self.width, self.height, format, self.sample_count());
+        self.depth_stencil = Some((texture, format));
+        return self;
+    }
+
+    /**
+     * Renders with `sample_count` samples per pixel and resolves into the color texture at the
+     * end of every pass. `texture_format` must be the color texture's format.
+     * Materials drawn into this target need the same PipelineSpecification::sample_count.
+     */
+    pub fn with_msaa(
+        mut self,
+        device: Device,
+        sample_count: SampleCount,
+        texture_format: TextureFormat,
+    ) -> Self {
+        assert!(
+            !self.is_screen(),
+            "Screen targets can't have attachments yet"
+        );
+        let texture = Texture::attachment(
+            device.clone(),
+            self.width,
+            self.height,
+            texture_format,
+            sample_count,
+        );
+        self.multisample = Some((texture, sample_count));
+        // The depth-stencil attachment must match the color sample count
+        if let Some((_, format)) = self.depth_stencil.take() {
+            self = self.with_depth_stencil(device, format);
+        }
+        return self;
+    }
+
+    pub fn sample_count(&self) -> SampleCount {
+        match &self.multisample {
+            Some((_, sample_count)) => *sample_count,
+            None => SampleCount::NoMultiSampling,
+        }
+    }
+
+    pub fn depth_stencil_format(&self) -> Option<TextureFormat> {
+        self.depth_stencil.as_ref().map(|(_, format)| *format)
+    }
+
     pub fn projection(&self) -> &glm::Mat4 {
         return self
             .projection
@@ -121,24 +274,62 @@ impl RenderTarget {
             .expect("Missing projection: Empty RenderTarget");
     }
 
-    pub fn color_target_info(&self) -> ColorTargetInfo {
+    pub fn color_target_info(&self, load_action: LoadAction) -> ColorTargetInfo {
         let texture = match &self.texture {
-            BackingTexture::Screen(texture) => texture
-                .as_ref()
-                .expect("Missing texture: Call to color_target_info on an empty RenderTarget"),
+            BackingTexture::Screen(texture) => {
+                &texture
+                    .as_ref()
+                    .expect("Missing texture: Call to color_target_info on an empty RenderTarget")
+                    .0
+            }
             BackingTexture::Texture(texture) => texture.inner(),
         };
 
-        let clear_color = match &self.texture {
-            BackingTexture::Screen(_) => CLEAR_COLOR_SCREEN,
-            BackingTexture::Texture(_) => CLEAR_COLOR,
-        };
-
-        ColorTargetInfo::default()
+        let info = ColorTargetInfo::default()
             .with_texture(texture)
             .with_store_op(StoreOp::STORE)
-            .with_load_op(LoadOp::CLEAR)
-            .with_clear_color(clear_color)
+            .with_load_op(load_op(load_action))
+            .with_clear_color(self.clear_color());
+        let Some((multisample, _)) = &self.multisample else {
+            return info;
+        };
+
+        // sdl3 has no setter for the resolve texture. ColorTargetInfo is a #[repr(C)] wrapper
+        // around SDL_GPUColorTargetInfo, so we fill that in directly.
+        unsafe {
+            let mut info: SDL_GPUColorTargetInfo = mem::transmute(info);
+            info.texture = multisample.inner().raw();
+            // Keep the samples too, a later LoadAction::Load pass continues from them
+            info.store_op = StoreOp::RESOLVE_AND_STORE;
+            info.resolve_texture = texture.raw();
+            return mem::transmute::<SDL_GPUColorTargetInfo, ColorTargetInfo>(info);
+        }
+    }
+
+    /**
+     * None without a depth-stencil attachment (see with_depth_stencil).
+     */
+    pub fn depth_stencil_target_info(
+        &self,
+        load_action: LoadAction,
+    ) -> Option<DepthStencilTargetInfo> {
+        let (texture, _) = self.depth_stencil.as_ref()?;
+        let info = SDL_GPUDepthStencilTargetInfo {
+            texture: texture.inner().raw(),
+            clear_depth: self.clear_depth,
+            load_op: load_op(load_action),
+            store_op: StoreOp::STORE,
+            stencil_load_op: load_op(load_action),
+            stencil_store_op: StoreOp::STORE,
+            cycle: false,
+            clear_stencil: self.clear_stencil,
+            ..Default::default()
+        };
+        // DepthStencilTargetInfo::with_texture wants a &mut to a texture we share,
+        // it is a #[repr(C)] wrapper around SDL_GPUDepthStencilTargetInfo too.
+        return Some(unsafe {
+            mem::transmute::<SDL_GPUDepthStencilTargetInfo, DepthStencilTargetInfo>(info)
+        });
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -150,6 +341,50 @@ impl RenderTarget {
         self.height = height;
         self.projection = Some(projection);
     }
+
+    /**
+     * Screen only: the swapchain size in pixels, larger than `width` x `height` on high density
+     * displays (window.size_in_pixels()). set_texture keeps it in sync with the swapchain texture.
+     */
+    pub fn resize_pixels(&mut self, width: u32, height: u32) {
+        if let BackingTexture::Texture(_) = self.texture {
+            panic!("Trying to resize non BackingTexture::Screen Render target")
+        }
+        self.pixel_width = width;
+        self.pixel_height = height;
+    }
+
+    /**
+     * (width, height) of the texture passes draw into.
+     */
+    pub fn pixel_size(&self) -> (u32, u32) {
+        return (self.pixel_width, self.pixel_height);
+    }
+
+    /**
+     * `rect`, in the target's `width` x `height` units, scaled to the pixels it covers.
+     */
+    pub fn to_pixels(&self, rect: Rect) -> Rect {
+        if self.width == 0 || self.height == 0 {
+            return rect;
+        }
+        let scale_x = self.pixel_width as f32 / self.width as f32;
+        let scale_y = self.pixel_height as f32 / self.height as f32;
+        // Round outwards so partially covered pixels are kept
+        let left = (rect.left() as f32 * scale_x).floor() as i32;
+        let top = (rect.top() as f32 * scale_y).floor() as i32;
+        let right = (rect.right() as f32 * scale_x).ceil() as i32;
+        let bottom = (rect.bottom() as f32 * scale_y).ceil() as i32;
+        return Rect::new(left, top, (right - left) as u32, (bottom - top) as u32);
+    }
+}
+
+fn load_op(load_action: LoadAction) -> LoadOp {
+    match load_action {
+        LoadAction::Clear => LoadOp::CLEAR,
+        LoadAction::Load => LoadOp::LOAD,
+        LoadAction::DontCare => LoadOp::DONT_CARE,
+    }
 }
 
 impl Drop for RenderTarget {
@@ -0,0 +1,372 @@
+use sdl3::gpu::{ShaderStage, VertexElementFormat};
+
+/**
+ * Resources an HLSL file declares for one stage, read from its `register(..)` annotations.
+ * Follows SDL's space allocation (https://wiki.libsdl.org/SDL3/SDL_CreateGPUShader):
+ *
+ *   vertex:   samplers in space0, uniform buffers in space1
+ *   fragment: samplers in space2, uniform buffers in space3
+ *
+ * This is a reader for the way our shaders are written, not an HLSL parser: macros, #include
+ * and resource arrays are not understood.
+ * TODO: storage textures and buffers (t/u registers without a sampler) are not counted.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderReflection {
+    pub uniform_buffer_count: u32,
+    pub sampler_count: u32,
+    // Vertex stage only: fields of vertex_main's input struct, by location (TEXCOORDn)
+    pub vertex_inputs: Vec<VertexInput>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexInput {
+    pub name: String,
+    pub location: u32,
+    // e.g. "float3"
+    pub ty: String,
+}
+
+impl ShaderReflection {
+    pub fn from_hlsl(source: &str, stage: ShaderStage) -> Result<Self, String> {
+        let source = strip_comments(source);
+        let (resource_space, uniform_space) = match stage {
+            ShaderStage::Fragment => (2, 3),
+            _ => (0, 1),
+        };
+
+        let mut reflection = ShaderReflection {
+            uniform_buffer_count: 0,
+            sampler_count: 0,
+            vertex_inputs: Vec::new(),
+        };
+        for (kind, index, space) in registers(&source)? {
+            match kind {
+                's' if space == resource_space => {
+                    reflection.sampler_count = reflection.sampler_count.max(index + 1)
+                }
+                'b' if space == uniform_space => {
+                    reflection.uniform_buffer_count = reflection.uniform_buffer_count.max(index + 1)
+                }
+                _ => {}
+            }
+        }
+        if stage == ShaderStage::Vertex {
+            reflection.vertex_inputs = vertex_inputs(&source)?;
+        }
+        return Ok(reflection);
+    }
+
+    /**
+     * Checks every input of the vertex shader is fed by one of `attributes` (location, format)
+     * with the same number of components.
+     */
+    pub fn validate_vertex_inputs(
+        &self,
+        attributes: &[(u32, VertexElementFormat)],
+    ) -> Result<(), String> {
+        for input in &self.vertex_inputs {
+            let Some((_, format)) = attributes
+                .iter()
+                .find(|(location, _)| *location == input.location)
+            else {
+                return Err(format!(
+                    "Vertex input {} (TEXCOORD{}) has no matching Vertex attribute",
+                    input.name, input.location
+                ));
+            };
+            let expected = components(*format);
+            let actual = type_components(&input.ty);
+            if actual != Some(expected) {
+                return Err(format!(
+                    "Vertex input {} (TEXCOORD{}) is {}, the Vertex attribute is {:?}",
+                    input.name, input.location, input.ty, format
+                ));
+            }
+        }
+        return Ok(());
+    }
+}
+
+// (register kind, index, space) of every `register(x0, spaceN)`
+fn registers(source: &str) -> Result<Vec<(char, u32, u32)>, String> {
+    let mut registers = Vec::new();
+    for (start, _) in source.match_indices("register(") {
+        let arguments = &source[start + "register(".len()..];
+        let Some(end) = arguments.find(')') else {
+            return Err(format!("Unclosed register at byte {}", start));
+        };
+        let mut arguments = arguments[..end].split(',').map(str::trim);
+
+        let register = arguments.next().unwrap_or_default();
+        let mut chars = register.chars();
+        let kind = chars.next().map(|c| c.to_ascii_lowercase());
+        let index = chars.as_str().parse::<u32>().ok();
+        let space = match arguments.next() {
+            Some(space) => space.strip_prefix("space").and_then(|n| n.parse().ok()),
+            None => Some(0),
+        };
+        match (kind, index, space) {
+            (Some(kind), Some(index), Some(space)) => registers.push((kind, index, space)),
+            _ => {
+                return Err(format!(
+                    "Unexpected register({})",
+                    &source[start + 9..start + 9 + end]
+                ));
+            }
+        }
+    }
+    return Ok(registers);
+}
+
+fn vertex_inputs(source: &str) -> Result<Vec<VertexInput>, String> {
+    let Some(entry) = source.find("vertex_main(") else {
+        return Err("No vertex_main entry point".to_string());
+    };
+    let parameters = &source[entry + "vertex_main(".len()..];
+    let input_type = parameters
+        .split(|c: char| c.is_whitespace() || c == ')')
+        .find(|token| !token.is_empty())
+        .ok_or("vertex_main takes no input")?;
+
+    let Some(body) = struct_body(source, input_type) else {
+        return Err(format!("Could not find struct {}", input_type));
+    };
+
+    let mut inputs = Vec::new();
+    for field in body
+        .split(';')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+    {
+        let (declaration, semantic) = field
+            .split_once(':')
+            .ok_or(format!("{}: field without semantic: {}", input_type, field))?;
+        let mut declaration = declaration.split_whitespace();
+        let (Some(ty), Some(name)) = (declaration.next(), declaration.next()) else {
+            return Err(format!("{}: unexpected field: {}", input_type, field));
+        };
+        let semantic = semantic.trim().to_ascii_uppercase();
+        let Some(location) = semantic.strip_prefix("TEXCOORD").and_then(|n| {
+            if n.is_empty() {
+                Some(0)
+            } else {
+                n.parse().ok()
+            }
+        }) else {
+            // SPIR-V cross-compilation only maps TEXCOORDn to vertex attribute locations
+            return Err(format!(
+                "{}.{}: expected a TEXCOORDn semantic, found {}",
+                input_type, name, semantic
+            ));
+        };
+        inputs.push(VertexInput {
+            name: name.to_string(),
+            location,
+            ty: ty.to_string(),
+        });
+    }
+    return Ok(inputs);
+}
+
+fn struct_body<'a>(source: &'a str, name: &str) -> Option<&'a str> {
+    let mut search = source;
+    while let Some(start) = search.find("struct") {
+        let rest = &search[start + "struct".len()..];
+        let open = rest.find('{')?;
+        if rest[..open].trim() == name {
+            let body = &rest[open + 1..];
+            return Some(&body[..body.find('}')?]);
+        }
+        search = rest;
+    }
+    return None;
+}
+
+fn strip_comments(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut rest = source;
+    loop {
+        let line = rest.find("//");
+        let block = rest.find("/*");
+        let (start, end_marker) = match (line, block) {
+            (Some(line), Some(block)) if block < line => (block, "*/"),
+            (Some(line), _) => (line, "\n"),
+            (None, Some(block)) => (block, "*/"),
+            (None, None) => break,
+        };
+        stripped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match rest.find(end_marker) {
+            Some(end) if end_marker == "\n" => rest = &rest[end..],
+            Some(end) => rest = &rest[end + end_marker.len()..],
+            None => rest = "",
+        }
+    }
+    stripped.push_str(rest);
+    return stripped;
+}
+
+fn components(format: VertexElementFormat) -> u32 {
+    match format {
+        VertexElementFormat::Float | VertexElementFormat::Int | VertexElementFormat::Uint => 1,
+        VertexElementFormat::Float2
+        | VertexElementFormat::Int2
+        | VertexElementFormat::Uint2
+        | VertexElementFormat::Half2
+        | VertexElementFormat::Short2
+        | VertexElementFormat::Short2Norm
+        | VertexElementFormat::Ushort2
+        | VertexElementFormat::Ushort2Norm
+        | VertexElementFormat::Byte2
+        | VertexElementFormat::Byte2Norm
+        | VertexElementFormat::Ubyte2
+        | VertexElementFormat::Ubyte2Norm => 2,
+        VertexElementFormat::Float3 | VertexElementFormat::Int3 | VertexElementFormat::Uint3 => 3,
+        _ => 4,
+    }
+}
+
+// float3 -> 3, float -> 1, None for anything that is not a scalar or vector
+fn type_components(ty: &str) -> Option<u32> {
+    let digits = ["float", "half", "int", "uint", "min16float"]
+        .iter()
+        .find_map(|scalar| ty.strip_prefix(scalar))?;
+    if digits.is_empty() {
+        return Some(1);
+    }
+    return digits.parse().ok().filter(|n| (1..=4).contains(n));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = "
+        Texture2D Texture : register(t0, space2);
+        SamplerState Sampler : register(s0, space2);
+        SamplerState Mask : register(S1, space2); // Case doesn't matter
+        cbuffer Matrices : register(b0, space1) { float4x4 Matrix; };
+        /* cbuffer Unused : register(b1, space1) { float4 Unused; }; */
+        // cbuffer Unused : register(b1, space3) { float4 Unused; };
+        cbuffer Fragment : register(b0, space3) { float4 Tint; };
+
+        struct VsInput
+        {
+            float3 Position : TEXCOORD0;
+            float4 Color : texcoord1; // float4 Commented : TEXCOORD9;
+            float2 TexCoord : TEXCOORD2;
+        };
+
+        VsOutput vertex_main(VsInput input) { }
+    ";
+
+    const ATTRIBUTES: [(u32, VertexElementFormat); 3] = [
+        (0, VertexElementFormat::Float3),
+        (1, VertexElementFormat::Ubyte4Norm),
+        (2, VertexElementFormat::Float2),
+    ];
+
+    fn vertex(source: &str) -> Result<ShaderReflection, String> {
+        ShaderReflection::from_hlsl(source, ShaderStage::Vertex)
+    }
+
+    #[test]
+    fn counts_the_registers_of_each_stage() {
+        let vertex = vertex(SHADER).unwrap();
+        let fragment = ShaderReflection::from_hlsl(SHADER, ShaderStage::Fragment).unwrap();
+        assert_eq!((vertex.uniform_buffer_count, vertex.sampler_count), (1, 0));
+        assert_eq!(
+            (fragment.uniform_buffer_count, fragment.sampler_count),
+            (1, 2)
+        );
+        assert!(fragment.vertex_inputs.is_empty());
+    }
+
+    #[test]
+    fn reads_the_vertex_inputs_by_location() {
+        let inputs: Vec<(String, u32, String)> = vertex(SHADER)
+            .unwrap()
+            .vertex_inputs
+            .into_iter()
+            .map(|input| (input.name, input.location, input.ty))
+            .collect();
+        assert_eq!(
+            inputs,
+            [
+                ("Position".to_string(), 0, "float3".to_string()),
+                ("Color".to_string(), 1, "float4".to_string()),
+                ("TexCoord".to_string(), 2, "float2".to_string()),
+            ]
+        );
+        vertex(SHADER)
+            .unwrap()
+            .validate_vertex_inputs(&ATTRIBUTES)
+            .unwrap();
+    }
+
+    #[test]
+    fn rejects_vertex_inputs_the_attributes_dont_feed() {
+        let reflection = vertex(SHADER).unwrap();
+        let mut attributes = ATTRIBUTES;
+        attributes[2].1 = VertexElementFormat::Float3;
+        assert_eq!(
+            reflection.validate_vertex_inputs(&attributes),
+            Err(
+                "Vertex input TexCoord (TEXCOORD2) is float2, the Vertex attribute is Float3"
+                    .into()
+            )
+        );
+        assert_eq!(
+            reflection.validate_vertex_inputs(&ATTRIBUTES[..2]),
+            Err("Vertex input TexCoord (TEXCOORD2) has no matching Vertex attribute".into())
+        );
+    }
+
+    #[test]
+    fn reports_what_it_cant_read() {
+        assert_eq!(
+            vertex("cbuffer Matrices : register(b0, spaceX) {};").unwrap_err(),
+            "Unexpected register(b0, spaceX)"
+        );
+        assert_eq!(
+            vertex("float4 fragment_main() : SV_Target0 {}").unwrap_err(),
+            "No vertex_main entry point"
+        );
+        assert_eq!(
+            vertex("struct In { float3 Position : POSITION; }; void vertex_main(In input) {}")
+                .unwrap_err(),
+            "In.Position: expected a TEXCOORDn semantic, found POSITION"
+        );
+    }
+
+    #[test]
+    fn reflects_the_shaders_we_ship() {
+        let attributes = [
+            (0, VertexElementFormat::Float3),
+            (1, VertexElementFormat::Ubyte4Norm),
+            (2, VertexElementFormat::Float2),
+            (3, VertexElementFormat::Ubyte4Norm),
+        ];
+        for source in [
+            include_str!("../shaders/default.hlsl"),
+            include_str!("../shaders/red.hlsl"),
+        ] {
+            let vertex = vertex(source).unwrap();
+            assert_eq!((vertex.uniform_buffer_count, vertex.sampler_count), (1, 0));
+            vertex.validate_vertex_inputs(&attributes).unwrap();
+            let fragment = ShaderReflection::from_hlsl(source, ShaderStage::Fragment).unwrap();
+            assert_eq!(fragment.sampler_count, 1);
+        }
+    }
+
+    #[test]
+    fn counts_components_of_scalars_and_vectors() {
+        assert_eq!(type_components("float"), Some(1));
+        assert_eq!(type_components("uint3"), Some(3));
+        assert_eq!(type_components("min16float4"), Some(4));
+        assert_eq!(type_components("float4x4"), None);
+        assert_eq!(type_components("float5"), None);
+        assert_eq!(type_components("Texture2D"), None);
+    }
+}
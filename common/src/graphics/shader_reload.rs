@@ -0,0 +1,194 @@
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant, SystemTime},
+};
+
+use sdl3::gpu::{Shader, ShaderFormat, ShaderStage};
+
+use crate::graphics::material::{ShaderSpecification, reflect};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/**
+ * Command line compiling one stage of an HLSL file, run without a shell.
+ * `{input}`, `{output}`, `{entry}` and `{stage}` (vertex / fragment) are substituted in every argument,
+ * the output format is picked by the `{output}` extension: .spv, .dxil or .msl.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShaderCompiler(pub &'static [&'static str]);
+
+impl ShaderCompiler {
+    // Same tool compile.sh uses, see its header to build it
+    pub const SHADERCROSS: ShaderCompiler = ShaderCompiler(&[
+        "shadercross",
+        "{input}",
+        "-e",
+        "{entry}",
+        "-t",
+        "{stage}",
+        "-s",
+        "HLSL",
+        "-o",
+        "{output}",
+    ]);
+
+    /**
+     * Returns the compiled code, or the compiler's output when it fails.
+     */
+    pub fn compile(
+        &self,
+        input: &Path,
+        output: &Path,
+        stage: ShaderStage,
+    ) -> Result<Vec<u8>, String> {
+        let (program, args) = self
+            .0
+            .split_first()
+            .ok_or("Empty shader compiler command")?;
+        let stage = match stage {
+            ShaderStage::Fragment => "fragment",
+            _ => "vertex",
+        };
+        let entry = format!("{}_main", stage);
+        let substitute = |arg: &str| {
+            arg.replace("{input}", &input.to_string_lossy())
+                .replace("{output}", &output.to_string_lossy())
+                .replace("{entry}", &entry)
+                .replace("{stage}", stage)
+        };
+
+        let result = Command::new(program)
+            .args(args.iter().map(|arg| substitute(arg)))
+            .output()
+            .map_err(|e| format!("Could not run {}: {}", program, e))?;
+        if !result.status.success() {
+            return Err(format!(
+                "{}{}",
+                String::from_utf8_lossy(&result.stdout),
+                String::from_utf8_lossy(&result.stderr)
+            ));
+        }
+        return std::fs::read(output)
+            .map_err(|e| format!("Could not read {}: {}", output.display(), e));
+    }
+}
+
+/**
+ * HLSL file (vertex_main + fragment_main) a material is rebuilt from whenever it changes.
+ * The compiled code in ShaderSpecification is still what the material starts with.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShaderHotReload {
+    pub path: &'static str,
+    pub compiler: ShaderCompiler,
+}
+
+/**
+ * Runtime side of ShaderHotReload, shared by every clone of a Material.
+ */
+pub(crate) struct ShaderWatch {
+    source: ShaderHotReload,
+    vertex: ShaderSpecification,
+    fragment: ShaderSpecification,
+    last_modified: Cell<SystemTime>,
+    last_poll: Cell<Instant>,
+}
+
+impl ShaderWatch {
+    pub fn new(
+        source: ShaderHotReload,
+        vertex: ShaderSpecification,
+        fragment: ShaderSpecification,
+    ) -> Self {
+        ShaderWatch {
+            last_modified: Cell::new(modified(source.path)),
+            last_poll: Cell::new(Instant::now()),
+            source,
+            vertex,
+            fragment,
+        }
+    }
+
+    pub fn path(&self) -> &'static str {
+        self.source.path
+    }
+
+    /**
+     * True once each time the source changed. Checks the file at most every POLL_INTERVAL.
+     */
+    pub fn poll(&self) -> bool {
+        if self.last_poll.get().elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll.set(Instant::now());
+
+        let modified = modified(self.source.path);
+        if modified <= self.last_modified.get() {
+            return false;
+        }
+        self.last_modified.set(modified);
+        return true;
+    }
+
+    /**
+     * Compiles both stages to a format `device` accepts and creates the shaders (vertex, fragment).
+     */
+    pub fn compile(
+        &self,
+        device: &sdl3::gpu::Device,
+        material: &str,
+    ) -> Result<(Shader, Shader), String> {
+        let supported_formats = device.get_shader_formats();
+        let (format, extension) = [
+            (ShaderFormat::SPIRV, "spv"),
+            (ShaderFormat::DXIL, "dxil"),
+            (ShaderFormat::MSL, "msl"),
+        ]
+        .into_iter()
+        .find(|(format, _)| (supported_formats & *format) == *format)
+        .ok_or(format!("No format to compile to ({:?})", supported_formats))?;
+
+        // The declarations may have changed along with the code
+        let source = std::fs::read_to_string(self.source.path)
+            .map_err(|e| format!("Could not read {}: {}", self.source.path, e))?;
+        let (vertex, fragment) = reflect(&source, self.vertex, self.fragment)?;
+
+        let compile = |specification: &ShaderSpecification, stage: ShaderStage| {
+            let output = output_path(material, stage, extension);
+            let code = self
+                .source
+                .compiler
+                .compile(Path::new(self.source.path), &output, stage)?;
+            let _ = std::fs::remove_file(&output);
+            specification
+                .create_with_code(device, format, &code, stage)
+                .map_err(|e| format!("{:?} shader rejected: {}", stage, e))
+        };
+        let vertex = compile(&vertex, ShaderStage::Vertex)?;
+        let fragment = compile(&fragment, ShaderStage::Fragment)?;
+        return Ok((vertex, fragment));
+    }
+}
+
+// Missing files (e.g. running away from the workspace) never look modified
+fn modified(path: &str) -> SystemTime {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+fn output_path(material: &str, stage: ShaderStage, extension: &str) -> PathBuf {
+    let name: String = material
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    std::env::temp_dir().join(format!(
+        "{}-{}-{:?}.{}",
+        name,
+        std::process::id(),
+        stage,
+        extension
+    ))
+}
@@ -21,6 +21,29 @@ impl Subtexture {
         Self { texture, rect, uvs }
     }
 
+    /**
+     * The whole texture.
+     */
+    pub fn whole(texture: Texture) -> Self {
+        let rect = Rect::new(0, 0, texture.width(), texture.height());
+        return Subtexture::new(texture, rect);
+    }
+
+    /**
+     * A part of this subtexture, `rect` is relative to its top left corner.
+     */
+    pub fn region(&self, rect: Rect) -> Subtexture {
+        return Subtexture::new(
+            self.texture.clone(),
+            Rect::new(
+                self.rect.x + rect.x,
+                self.rect.y + rect.y,
+                rect.w as u32,
+                rect.h as u32,
+            ),
+        );
+    }
+
     pub fn flip(&mut self, flip_x: bool, flip_y: bool) {
         if flip_x {
             self.uvs.x += self.uvs.w;
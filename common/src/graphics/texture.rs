@@ -1,142 +1,463 @@
-use std::{path::Path, rc::Rc};
+use std::{
+    cell::{Cell, Ref, RefCell, RefMut},
+    fmt::Display,
+    path::Path,
+    rc::Rc,
+};
+
+use sdl3::{
+    gpu::{
+        self, CommandBuffer, CopyPass, Filter, Sampler, SamplerAddressMode, SamplerCreateInfo,
+        SamplerMipmapMode, TextureCreateInfo, TextureFormat, TextureRegion, TextureSamplerBinding,
+        TextureTransferInfo, TransferBuffer,
+    },
+    rect::Rect,
+    sys::gpu::{
+        SDL_DownloadFromGPUTexture, SDL_GPUTextureRegion, SDL_GPUTextureTransferInfo,
+        SDL_GenerateMipmapsForGPUTexture,
+    },
+};
 
-use sdl3::gpu::{
-    self, CopyPass, Device, Sampler, SamplerCreateInfo, TextureCreateInfo, TextureFormat,
-    TextureRegion, TextureSamplerBinding, TextureTransferInfo, TransferBuffer,
+use crate::graphics::{
+    device::Device,
+    pixel_format::{Image, ImagePixels, bytes_per_pixel},
 };
 
 static mut NEXT_ID: u16 = 0;
 
+#[derive(Debug)]
+pub enum TextureError {
+    // stb_image could not read or decode the image
+    Decode(String),
+    // Images can't be converted to this format, see Image::convert
+    UnsupportedFormat(TextureFormat),
+    // Textures are at most 65535 pixels on a side
+    TooLarge { width: u32, height: u32 },
+}
+
+impl Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::Decode(message) => write!(f, "Could not decode image: {}", message),
+            TextureError::UnsupportedFormat(format) => {
+                write!(f, "Images can't be loaded as {:?}", format)
+            }
+            TextureError::TooLarge { width, height } => {
+                write!(f, "Image is too large: {}x{}", width, height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+/**
+ * How a Texture handle is sampled, see Texture::with_sampler.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerDescription {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    // Only matters for textures with mipmaps
+    pub mipmap_mode: SamplerMipmapMode,
+    pub address_mode_u: SamplerAddressMode,
+    pub address_mode_v: SamplerAddressMode,
+    // None disables anisotropic filtering
+    pub max_anisotropy: Option<f32>,
+}
+
+impl SamplerDescription {
+    // Pixel art: nearest texel, clamped to the edges
+    pub const DEFAULT: SamplerDescription = SamplerDescription {
+        min_filter: Filter::Nearest,
+        mag_filter: Filter::Nearest,
+        mipmap_mode: SamplerMipmapMode::Nearest,
+        address_mode_u: SamplerAddressMode::ClampToEdge,
+        address_mode_v: SamplerAddressMode::ClampToEdge,
+        max_anisotropy: None,
+    };
+
+    // Smooth scaling (trilinear with mipmaps)
+    pub const LINEAR: SamplerDescription = SamplerDescription {
+        min_filter: Filter::Linear,
+        mag_filter: Filter::Linear,
+        mipmap_mode: SamplerMipmapMode::Linear,
+        ..SamplerDescription::DEFAULT
+    };
+}
+
+pub struct TextureSpecification {
+    pub format: TextureFormat,
+    pub sampler: SamplerDescription,
+    // Allocates the whole mip chain, regenerated from the first level after every upload
+    // and every draw_into the texture
+    pub mipmaps: bool,
+}
+
+impl TextureSpecification {
+    pub const DEFAULT: TextureSpecification = TextureSpecification {
+        format: TextureFormat::R8g8b8a8Unorm,
+        sampler: SamplerDescription::DEFAULT,
+        mipmaps: false,
+    };
+}
+
 /**
  * Lightweight handle wrapping around a sdl::Texture + sdl::Sampler.
+ * The pixels are shared by every clone, the sampler belongs to the handle (see with_sampler).
+ * On a headless Device there is no GPU texture, the pixels live in CPU memory instead.
  */
 #[derive(Clone)]
 pub struct Texture {
     pub id: u16,
-    needs_upload: bool,
-    inner: Rc<(sdl3::gpu::Texture<'static>, Sampler, TransferBuffer)>,
+    inner: Rc<TextureStorage>,
+    sampler: Rc<TextureSampler>,
 }
 
+struct TextureSampler {
+    description: SamplerDescription,
+    // None for headless textures and attachments
+    sampler: Option<Sampler>,
+}
+
+enum TextureStorage {
+    Gpu {
+        texture: sdl3::gpu::Texture<'static>,
+        // Creates the samplers of with_sampler, and reads the pixels back
+        device: sdl3::gpu::Device,
+        format: TextureFormat,
+        transfer_buffer: TransferBuffer,
+        mip_levels: u32,
+        // Levels past the first no longer match it
+        mipmaps_stale: Cell<bool>,
+        bytes_per_pixel: u32,
+        // CPU copy of the pixels, allocated by the first write: the transfer buffer may be
+        // cycled (or still in use by the GPU) so it can't hold them between uploads
+        pixels: RefCell<Vec<u8>>,
+        // Written since the last upload, shared by every handle
+        dirty: Cell<Option<Rect>>,
+    },
+    // Render pass only (depth-stencil, multisampled color): never sampled nor uploaded
+    Attachment(sdl3::gpu::Texture<'static>),
+    Cpu {
+        width: u32,
+        height: u32,
+        // RGBA8, row-major
+        pixels: RefCell<Vec<u8>>,
+    },
+}
+
+// Handles sampled differently are different textures to a Batch
 impl PartialEq for Texture {
     fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+        self.id == other.id && self.sampler.description == other.sampler.description
     }
 }
 
 impl Texture {
+    /**
+     * R8g8b8a8Unorm texture from an encoded image (png, jpg, ...), panics if it can't be decoded.
+     */
     pub fn from_bytes(device: Device, bytes: &[u8]) -> Self {
-        let load_result = stb_image::image::load_from_memory(bytes);
+        return Texture::try_from_bytes(device, bytes, &TextureSpecification::DEFAULT)
+            .expect("Could not load texture");
+    }
+
+    /**
+     * R8g8b8a8Unorm texture from an image file, panics if it can't be read or decoded.
+     */
+    pub fn from_path<P: AsRef<Path>>(device: Device, path: P) -> Self {
+        return Texture::try_from_path(device, path, &TextureSpecification::DEFAULT)
+            .expect("Could not load texture");
+    }
+
+    /**
+     * Decodes any image stb_image reads (gray, gray + alpha, RGB, RGBA or HDR) and converts it
+     * to the specification's format, see Image::convert for the supported formats.
+     * Pick an *Srgb format for colors authored in sRGB (most pngs) so they are sampled linear,
+     * and a Unorm one for data (masks, normals, lookup tables).
+     * Headless textures are always RGBA8: the format is only checked there.
+     */
+    pub fn try_from_bytes(
+        device: Device,
+        bytes: &[u8],
+        specification: &TextureSpecification,
+    ) -> Result<Self, TextureError> {
+        let image = decode_bytes(bytes)?;
+        return Texture::from_image(device, &image, specification);
+    }
+
+    pub fn try_from_path<P: AsRef<Path>>(
+        device: Device,
+        path: P,
+        specification: &TextureSpecification,
+    ) -> Result<Self, TextureError> {
+        let image = decode(stb_image::image::load(&path)).map_err(|error| match error {
+            TextureError::Decode(message) => {
+                TextureError::Decode(format!("{}: {}", path.as_ref().display(), message))
+            }
+            error => error,
+        })?;
+        return Texture::from_image(device, &image, specification);
+    }
+
+    /**
+     * Like try_from_path, but logs the error and returns Texture::fallback instead:
+     * for assets that are reloaded while the game runs, a broken file shows up as a checkerboard.
+     */
+    pub fn from_path_or_fallback<P: AsRef<Path>>(
+        device: Device,
+        path: P,
+        specification: &TextureSpecification,
+    ) -> Self {
+        match Texture::try_from_path(device.clone(), path, specification) {
+            Ok(texture) => texture,
+            Err(error) => {
+                eprintln!("{}", error);
+                Texture::fallback(device)
+            }
+        }
+    }
 
-        let image = match load_result {
-            stb_image::image::LoadResult::Error(_) => panic!("Could not load image path"),
-            stb_image::image::LoadResult::ImageU8(image) => image,
-            stb_image::image::LoadResult::ImageF32(_image) => {
-                panic!("Only u8 images are supported")
+    /**
+     * 8x8 magenta and black checkerboard (R8g8b8a8Unorm), stands in for textures that failed to load.
+     */
+    pub fn fallback(device: Device) -> Self {
+        let mut pixels = Vec::with_capacity(8 * 8 * 4);
+        for y in 0..8 {
+            for x in 0..8 {
+                let color = if (x / 4 + y / 4) % 2 == 0 {
+                    [255, 0, 255, 255]
+                } else {
+                    [0, 0, 0, 255]
+                };
+                pixels.extend_from_slice(&color);
             }
+        }
+        let image = Image {
+            width: 8,
+            height: 8,
+            channels: 4,
+            pixels: ImagePixels::U8(pixels),
         };
+        return Texture::from_image(device, &image, &TextureSpecification::DEFAULT)
+            .expect("The fallback texture is RGBA8");
+    }
+
+    fn from_image(
+        device: Device,
+        image: &Image,
+        specification: &TextureSpecification,
+    ) -> Result<Self, TextureError> {
+        if image.width > u16::MAX as u32 || image.height > u16::MAX as u32 {
+            return Err(TextureError::TooLarge {
+                width: image.width,
+                height: image.height,
+            });
+        }
+        let mut data = image
+            .convert(specification.format)
+            .ok_or(TextureError::UnsupportedFormat(specification.format))?;
+        if let Device::Headless = device {
+            data = image
+                .convert(TextureFormat::R8g8b8a8Unorm)
+                .expect("R8g8b8a8Unorm is always supported");
+        }
 
-        let mut texture = Texture::new(
+        let texture = Texture::from_specification(
             device.clone(),
             image.width as u16,
             image.height as u16,
-            // TextureFormat::B8g8r8a8Unorm, // TODO
-            TextureFormat::R8g8b8a8Unorm,
+            specification,
         );
+        texture.write_pixels(&data);
 
-        let (_, _, transfer_buffer) = texture.inner.as_ref();
-        texture.needs_upload = true;
-        let mut map = transfer_buffer.map(&device, true);
-        let memory = map.mem_mut();
-        memory[..image.data.len()].copy_from_slice(&image.data);
-        map.unmap();
+        return Ok(texture);
+    }
 
-        return texture;
+    /**
+     * Overwrites every pixel, see write_region.
+     */
+    pub fn write_pixels(&self, data: &[u8]) {
+        self.write_region(Rect::new(0, 0, self.width(), self.height()), data);
     }
 
-    pub fn from_path<P: AsRef<Path>>(device: Device, path: P) -> Self {
-        let load_result = stb_image::image::load(path);
+    /**
+     * Copies `data` (tightly packed rows of `region`, in the texture's format) into `region`.
+     * GPU textures only upload the written pixels, by the next Batch::draw_into that uses them:
+     * writing a few pixels every frame (minimaps, paint tools, glyphs) is cheap.
+     * Headless textures are always RGBA8.
+     */
+    pub fn write_region(&self, region: Rect, data: &[u8]) {
+        assert!(
+            region.x >= 0
+                && region.y >= 0
+                && region.x as u32 + region.w as u32 <= self.width()
+                && region.y as u32 + region.h as u32 <= self.height(),
+            "Region {:?} is outside of the {}x{} texture",
+            region,
+            self.width(),
+            self.height()
+        );
 
-        let image = match load_result {
-            stb_image::image::LoadResult::Error(_) => panic!("Could not load image path"),
-            stb_image::image::LoadResult::ImageU8(image) => image,
-            stb_image::image::LoadResult::ImageF32(_image) => {
-                panic!("Only u8 images are supported")
+        let (pixels, bytes_per_pixel) = match self.inner.as_ref() {
+            TextureStorage::Gpu {
+                pixels,
+                bytes_per_pixel,
+                dirty,
+                ..
+            } => {
+                let dirty_region = match dirty.get() {
+                    Some(dirty_region) => dirty_region.union(region),
+                    None => region,
+                };
+                dirty.set(Some(dirty_region));
+                (pixels, *bytes_per_pixel)
             }
+            TextureStorage::Cpu { pixels, .. } => (pixels, 4),
+            TextureStorage::Attachment(_) => panic!("Trying to write pixels to an attachment"),
         };
 
-        let mut texture = Texture::new(
-            device.clone(),
-            image.width as u16,
-            image.height as u16,
-            // TextureFormat::B8g8r8a8Unorm, // TODO
-            TextureFormat::R8g8b8a8Unorm,
+        let row_size = (region.w as u32 * bytes_per_pixel) as usize;
+        assert_eq!(
+            data.len(),
+            row_size * region.h as usize,
+            "Expected {}x{} pixels of {} bytes",
+            region.w,
+            region.h,
+            bytes_per_pixel
         );
 
-        let (_, _, transfer_buffer) = texture.inner.as_ref();
-        texture.needs_upload = true;
-        let mut map = transfer_buffer.map(&device, true);
-        let memory = map.mem_mut();
-        memory[..image.data.len()].copy_from_slice(&image.data);
-        map.unmap();
-
-        return texture;
+        let texture_row_size = (self.width() * bytes_per_pixel) as usize;
+        let mut pixels = pixels.borrow_mut();
+        if pixels.is_empty() {
+            pixels.resize(texture_row_size * self.height() as usize, 0);
+        }
+        for (row, source) in data.chunks_exact(row_size).enumerate() {
+            let start = (region.y as usize + row) * texture_row_size
+                + region.x as usize * bytes_per_pixel as usize;
+            pixels[start..start + row_size].copy_from_slice(source);
+        }
     }
 
-    pub fn upload(&mut self, pass: &CopyPass) {
-        if !self.needs_upload {
+    /**
+     * Uploads the pixels written since the last upload, if any.
+     */
+    pub fn upload(&self, device: &sdl3::gpu::Device, pass: &CopyPass) {
+        let TextureStorage::Gpu {
+            texture,
+            transfer_buffer,
+            bytes_per_pixel,
+            pixels,
+            dirty,
+            mipmaps_stale,
+            ..
+        } = self.inner.as_ref()
+        else {
             return;
+        };
+        let Some(region) = dirty.take() else {
+            return;
+        };
+        mipmaps_stale.set(true);
+
+        // The dirty region goes at the start of the transfer buffer, tightly packed
+        let row_size = (region.w as u32 * *bytes_per_pixel) as usize;
+        let texture_row_size = (texture.width() * *bytes_per_pixel) as usize;
+        {
+            let pixels = pixels.borrow();
+            let mut map = transfer_buffer.map::<u8>(device, true);
+            let memory = map.mem_mut();
+            for row in 0..region.h as usize {
+                let start = (region.y as usize + row) * texture_row_size
+                    + region.x as usize * *bytes_per_pixel as usize;
+                memory[row * row_size..(row + 1) * row_size]
+                    .copy_from_slice(&pixels[start..start + row_size]);
+            }
+            map.unmap();
         }
-        self.needs_upload = false;
 
-        let (texture, _, transfer_buffer) = self.inner.as_ref();
         pass.upload_to_gpu_texture(
             TextureTransferInfo::new()
                 .with_offset(0)
-                .with_pixels_per_row(texture.width() as u32)
-                .with_rows_per_layer(texture.height() as u32)
+                .with_pixels_per_row(region.w as u32)
+                .with_rows_per_layer(region.h as u32)
                 .with_transfer_buffer(&transfer_buffer),
             TextureRegion::new()
                 .with_texture(&texture)
                 .with_mip_level(0)
-                .with_x(0)
-                .with_y(0)
-                .with_width(texture.width() as u32)
-                .with_height(texture.height() as u32)
+                .with_x(region.x as u32)
+                .with_y(region.y as u32)
+                .with_width(region.w as u32)
+                .with_height(region.h as u32)
                 .with_depth(1),
             false,
         );
     }
 
     pub fn new(device: Device, width: u16, height: u16, texture_format: TextureFormat) -> Self {
+        return Texture::from_specification(
+            device,
+            width,
+            height,
+            &TextureSpecification {
+                format: texture_format,
+                ..TextureSpecification::DEFAULT
+            },
+        );
+    }
+
+    /**
+     * Headless textures have no mipmaps, and are sampled as if their filters were Nearest.
+     */
+    pub fn from_specification(
+        device: Device,
+        width: u16,
+        height: u16,
+        specification: &TextureSpecification,
+    ) -> Self {
+        let id = next_id();
+
+        // Only the formats Image::convert writes are uploaded from the CPU, the rest are render targets
+        let bytes_per_pixel = bytes_per_pixel(specification.format).unwrap_or(4);
+        let size = (width as u32 * height as u32) * bytes_per_pixel;
+        // Headless textures are always RGBA8
+        let cpu_size = (width as u32 * height as u32) * 4;
+
+        let Device::Gpu(device) = device else {
+            return Texture {
+                id,
+                inner: Rc::new(TextureStorage::Cpu {
+                    width: width as u32,
+                    height: height as u32,
+                    pixels: RefCell::new(vec![0; cpu_size as usize]),
+                }),
+                sampler: Rc::new(TextureSampler {
+                    description: specification.sampler,
+                    sampler: None,
+                }),
+            };
+        };
+
+        let mip_levels = if specification.mipmaps {
+            mip_level_count(width, height)
+        } else {
+            1
+        };
         let texture = device
             .create_texture(
                 TextureCreateInfo::new()
                     .with_type(gpu::TextureType::_2D)
-                    .with_format(texture_format)
+                    .with_format(specification.format)
                     .with_usage(gpu::TextureUsage::SAMPLER | gpu::TextureUsage::COLOR_TARGET)
                     .with_width(width as u32)
                     .with_height(height as u32)
                     .with_layer_count_or_depth(1)
-                    .with_num_levels(1)
+                    .with_num_levels(mip_levels)
                     .with_sample_count(gpu::SampleCount::NoMultiSampling),
             )
             .expect("Could not create texture.");
 
-        let sampler = device
-            .create_sampler(
-                SamplerCreateInfo::new()
-                    .with_min_filter(gpu::Filter::Nearest)
-                    .with_mag_filter(gpu::Filter::Nearest)
-                    .with_mipmap_mode(gpu::SamplerMipmapMode::Nearest)
-                    .with_address_mode_u(gpu::SamplerAddressMode::ClampToEdge)
-                    .with_address_mode_v(gpu::SamplerAddressMode::ClampToEdge)
-                    .with_address_mode_w(gpu::SamplerAddressMode::ClampToEdge)
-                    .with_enable_compare(false),
-            )
-            .expect("Could not create sampler");
-
-        let channels: u32 = 4;
-        let size = (width as u32 * height as u32) * channels;
         let transfer_buffer = device
             .create_transfer_buffer()
             .with_usage(sdl3::sys::gpu::SDL_GPUTransferBufferUsage::UPLOAD)
@@ -144,37 +465,456 @@ impl Texture {
             .build()
             .expect("Could not build transfer buffer");
 
-        let id = unsafe {
-            let id = NEXT_ID;
-            NEXT_ID += 1;
-            id
+        return Texture {
+            id,
+            sampler: Rc::new(TextureSampler {
+                description: specification.sampler,
+                sampler: Some(create_sampler(&device, &specification.sampler)),
+            }),
+            inner: Rc::new(TextureStorage::Gpu {
+                texture,
+                device,
+                format: specification.format,
+                transfer_buffer,
+                mip_levels,
+                mipmaps_stale: Cell::new(false),
+                bytes_per_pixel,
+                // Offscreen render_targets are never written from the CPU
+                pixels: RefCell::new(Vec::new()),
+                dirty: Cell::new(None),
+            }),
+        };
+    }
+
+    /**
+     * Another handle to the same pixels, sampled with `description`:
+     *
+     *   let background = Texture::from_bytes(device, BACKGROUND).with_sampler(SamplerDescription {
+     *       address_mode_u: SamplerAddressMode::Repeat,
+     *       address_mode_v: SamplerAddressMode::Repeat,
+     *       ..SamplerDescription::DEFAULT
+     *   });
+     *
+     * Creates a GPU sampler: keep the handle around rather than calling this every frame.
+     */
+    pub fn with_sampler(&self, description: SamplerDescription) -> Texture {
+        if self.sampler.description == description {
+            return self.clone();
+        }
+        let sampler = match self.inner.as_ref() {
+            TextureStorage::Gpu { device, .. } => Some(create_sampler(device, &description)),
+            TextureStorage::Attachment(_) => panic!("Attachments are not sampled"),
+            TextureStorage::Cpu { .. } => None,
+        };
+        return Texture {
+            id: self.id,
+            inner: self.inner.clone(),
+            sampler: Rc::new(TextureSampler {
+                description,
+                sampler,
+            }),
+        };
+    }
+
+    pub fn sampler_description(&self) -> SamplerDescription {
+        self.sampler.description
+    }
+
+    /**
+     * RGBA8 copy of the pixels. GPU textures are read back from the GPU (see download):
+     * CPU writes that no draw_into uploaded yet are not in there.
+     */
+    pub fn read_pixels(&self) -> Vec<u8> {
+        match self.inner.as_ref() {
+            TextureStorage::Gpu {
+                texture,
+                device,
+                format,
+                ..
+            } => download(device, texture, *format),
+            TextureStorage::Attachment(_) => panic!("Trying to read back an attachment"),
+            TextureStorage::Cpu { pixels, .. } => pixels.borrow().clone(),
+        }
+    }
+
+    /**
+     * Regenerates the mip chain from the first level if it was uploaded or drawn into since.
+     * Records into `command_buffer` outside of any pass: Batch calls it after the copy pass,
+     * and after rendering into the texture.
+     */
+    pub(crate) fn generate_mipmaps(&self, command_buffer: &CommandBuffer) {
+        let TextureStorage::Gpu {
+            texture,
+            mip_levels,
+            mipmaps_stale,
+            ..
+        } = self.inner.as_ref()
+        else {
+            return;
+        };
+        if *mip_levels > 1 && mipmaps_stale.replace(false) {
+            unsafe { SDL_GenerateMipmapsForGPUTexture(command_buffer.raw(), texture.raw()) };
+        }
+    }
+
+    // The first level was rendered into
+    pub(crate) fn mark_mipmaps_stale(&self) {
+        if let TextureStorage::Gpu { mipmaps_stale, .. } = self.inner.as_ref() {
+            mipmaps_stale.set(true);
+        }
+    }
+
+    /**
+     * Render pass attachment of a RenderTarget: a depth-stencil texture when `texture_format`
+     * is a depth format, a (multisampled) color texture otherwise.
+     * On a headless Device it only has a size, nothing draws into it.
+     */
+    pub fn attachment(
+        device: Device,
+        width: u32,
+        height: u32,
+        texture_format: TextureFormat,
+        sample_count: gpu::SampleCount,
+    ) -> Self {
+        let id = next_id();
+        let Device::Gpu(device) = device else {
+            return Texture {
+                id,
+                inner: Rc::new(TextureStorage::Cpu {
+                    width,
+                    height,
+                    pixels: RefCell::new(Vec::new()),
+                }),
+                sampler: Rc::new(TextureSampler {
+                    description: SamplerDescription::DEFAULT,
+                    sampler: None,
+                }),
+            };
         };
 
+        let usage = match texture_format {
+            TextureFormat::D16Unorm
+            | TextureFormat::D24Unorm
+            | TextureFormat::D32Float
+            | TextureFormat::D24UnormS8Uint
+            | TextureFormat::D32FloatS8Uint => gpu::TextureUsage::DEPTH_STENCIL_TARGET,
+            _ => gpu::TextureUsage::COLOR_TARGET,
+        };
+        let texture = device
+            .create_texture(
+                TextureCreateInfo::new()
+                    .with_type(gpu::TextureType::_2D)
+                    .with_format(texture_format)
+                    .with_usage(usage)
+                    .with_width(width)
+                    .with_height(height)
+                    .with_layer_count_or_depth(1)
+                    .with_num_levels(1)
+                    .with_sample_count(sample_count),
+            )
+            .expect("Could not create attachment texture.");
+
         return Texture {
             id,
-            inner: Rc::new((texture, sampler, transfer_buffer)),
-            needs_upload: false, // Offscreen render_targets don't need to be uploaded
+            inner: Rc::new(TextureStorage::Attachment(texture)),
+            sampler: Rc::new(TextureSampler {
+                description: SamplerDescription::DEFAULT,
+                sampler: None,
+            }),
         };
     }
 
+    /**
+     * GPU only, panics for textures created on a headless Device.
+     */
     pub fn inner(&self) -> &sdl3::gpu::Texture<'static> {
-        &self.inner.0
+        match self.inner.as_ref() {
+            TextureStorage::Gpu { texture, .. } => texture,
+            TextureStorage::Attachment(texture) => texture,
+            TextureStorage::Cpu { .. } => panic!("Trying to get the GPU texture of a CPU texture"),
+        }
+    }
+
+    /**
+     * RGBA8 pixels of a texture created on a headless Device, None for GPU textures.
+     */
+    pub fn pixels(&self) -> Option<Ref<'_, Vec<u8>>> {
+        match self.inner.as_ref() {
+            TextureStorage::Gpu { .. } | TextureStorage::Attachment(_) => None,
+            TextureStorage::Cpu { pixels, .. } => Some(pixels.borrow()),
+        }
+    }
+
+    /**
+     * Mutable access to the pixels of a CPU texture (software rendering), None for GPU textures.
+     */
+    pub fn pixels_mut(&self) -> Option<RefMut<'_, Vec<u8>>> {
+        match self.inner.as_ref() {
+            TextureStorage::Gpu { .. } | TextureStorage::Attachment(_) => None,
+            TextureStorage::Cpu { pixels, .. } => Some(pixels.borrow_mut()),
+        }
     }
 
     pub fn width(&self) -> u32 {
-        let (texture, _, _) = self.inner.as_ref();
-        texture.width()
+        match self.inner.as_ref() {
+            TextureStorage::Gpu { texture, .. } => texture.width(),
+            TextureStorage::Attachment(texture) => texture.width(),
+            TextureStorage::Cpu { width, .. } => *width,
+        }
     }
 
     pub fn height(&self) -> u32 {
-        let (texture, _, _) = self.inner.as_ref();
-        texture.height()
+        match self.inner.as_ref() {
+            TextureStorage::Gpu { texture, .. } => texture.height(),
+            TextureStorage::Attachment(texture) => texture.height(),
+            TextureStorage::Cpu { height, .. } => *height,
+        }
     }
 
+    /**
+     * GPU only, panics for textures created on a headless Device.
+     */
     pub fn bindings(&self) -> TextureSamplerBinding {
-        let (texture, sampler, _) = self.inner.as_ref();
+        let (TextureStorage::Gpu { texture, .. }, Some(sampler)) =
+            (self.inner.as_ref(), self.sampler.sampler.as_ref())
+        else {
+            panic!("Trying to bind a CPU texture or an attachment")
+        };
         return TextureSamplerBinding::new()
             .with_sampler(sampler)
             .with_texture(texture);
     }
 }
+
+/**
+ * Copies the first level of `texture` into RGBA8 rows.
+ * Blocks until the GPU finished every command submitted so far: fine for screenshots and
+ * captures, too slow to do every frame of a shipped game.
+ */
+pub(crate) fn download(
+    device: &sdl3::gpu::Device,
+    texture: &sdl3::gpu::Texture,
+    format: TextureFormat,
+) -> Vec<u8> {
+    let command_buffer = device.acquire_command_buffer().unwrap();
+    let download = Download::record(device, &command_buffer, texture, format);
+    let fence = command_buffer.submit_and_acquire_fence(device).unwrap();
+    device
+        .wait_fences(true, &[fence])
+        .expect("Could not wait for the download");
+    return download.pixels(device);
+}
+
+/**
+ * A copy of a texture's first level, recorded into a command buffer. Its pixels can be read
+ * once that command buffer was submitted and the GPU finished it (wait for its fence).
+ */
+pub struct Download {
+    transfer_buffer: TransferBuffer,
+    width: u32,
+    height: u32,
+    bgra: bool,
+}
+
+impl Download {
+    /**
+     * Records the copy in a copy pass of its own: call it outside of any pass, after the
+     * commands that draw into `texture`.
+     */
+    pub(crate) fn record(
+        device: &sdl3::gpu::Device,
+        command_buffer: &CommandBuffer,
+        texture: &sdl3::gpu::Texture,
+        format: TextureFormat,
+    ) -> Self {
+        let bgra = match format {
+            TextureFormat::R8g8b8a8Unorm | TextureFormat::R8g8b8a8UnormSrgb => false,
+            TextureFormat::B8g8r8a8Unorm | TextureFormat::B8g8r8a8UnormSrgb => true,
+            _ => panic!("Reading back {:?} textures is not supported", format),
+        };
+        let (width, height) = (texture.width(), texture.height());
+
+        let transfer_buffer = device
+            .create_transfer_buffer()
+            .with_usage(sdl3::sys::gpu::SDL_GPUTransferBufferUsage::DOWNLOAD)
+            .with_size(width * height * 4)
+            .build()
+            .expect("Could not build transfer buffer");
+        let copy_pass = device.begin_copy_pass(command_buffer).unwrap();
+        // sdl3-rs has no wrapper for downloads
+        unsafe {
+            SDL_DownloadFromGPUTexture(
+                copy_pass.raw(),
+                &SDL_GPUTextureRegion {
+                    texture: texture.raw(),
+                    w: width,
+                    h: height,
+                    d: 1,
+                    ..Default::default()
+                },
+                &SDL_GPUTextureTransferInfo {
+                    transfer_buffer: transfer_buffer.raw(),
+                    offset: 0,
+                    pixels_per_row: width,
+                    rows_per_layer: height,
+                },
+            )
+        };
+        device.end_copy_pass(copy_pass);
+
+        return Download {
+            transfer_buffer,
+            width,
+            height,
+            bgra,
+        };
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /**
+     * RGBA8 rows. Only once the GPU finished the command buffer the copy was recorded into:
+     * before that the transfer buffer holds garbage.
+     */
+    pub fn pixels(self, device: &sdl3::gpu::Device) -> Vec<u8> {
+        let map = self.transfer_buffer.map::<u8>(device, false);
+        let mut pixels = map.mem().to_vec();
+        map.unmap();
+        if self.bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        return pixels;
+    }
+}
+
+fn create_sampler(device: &sdl3::gpu::Device, description: &SamplerDescription) -> Sampler {
+    return device
+        .create_sampler(
+            SamplerCreateInfo::new()
+                .with_min_filter(description.min_filter)
+                .with_mag_filter(description.mag_filter)
+                .with_mipmap_mode(description.mipmap_mode)
+                .with_address_mode_u(description.address_mode_u)
+                .with_address_mode_v(description.address_mode_v)
+                .with_address_mode_w(gpu::SamplerAddressMode::ClampToEdge)
+                .with_enable_anisotropy(description.max_anisotropy.is_some())
+                .with_max_anisotropy(description.max_anisotropy.unwrap_or(1f32))
+                // Every level the texture has
+                .with_max_lod(1000f32)
+                .with_enable_compare(false),
+        )
+        .expect("Could not create sampler");
+}
+
+pub(crate) fn decode_bytes(bytes: &[u8]) -> Result<Image, TextureError> {
+    return decode(stb_image::image::load_from_memory(bytes));
+}
+
+fn decode(load_result: stb_image::image::LoadResult) -> Result<Image, TextureError> {
+    let image = match load_result {
+        stb_image::image::LoadResult::Error(message) => return Err(TextureError::Decode(message)),
+        stb_image::image::LoadResult::ImageU8(image) => Image {
+            width: image.width as u32,
+            height: image.height as u32,
+            channels: image.depth as u32,
+            pixels: ImagePixels::U8(image.data),
+        },
+        stb_image::image::LoadResult::ImageF32(image) => Image {
+            width: image.width as u32,
+            height: image.height as u32,
+            channels: image.depth as u32,
+            pixels: ImagePixels::F32(image.data),
+        },
+    };
+    return Ok(image);
+}
+
+// The whole mip chain, down to 1x1
+fn mip_level_count(width: u16, height: u16) -> u32 {
+    return u32::BITS - (width.max(height) as u32).leading_zeros();
+}
+
+fn next_id() -> u16 {
+    unsafe {
+        let id = NEXT_ID;
+        NEXT_ID += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RGBA8 pixel at (x, y)
+    fn pixel(texture: &Texture, x: usize, y: usize) -> [u8; 4] {
+        let pixels = texture.pixels().unwrap();
+        let index = (y * texture.width() as usize + x) * 4;
+        return pixels[index..index + 4].try_into().unwrap();
+    }
+
+    #[test]
+    fn fallback_is_a_checkerboard() {
+        let fallback = Texture::fallback(Device::Headless);
+        assert_eq!((fallback.width(), fallback.height()), (8, 8));
+        assert_eq!(pixel(&fallback, 0, 0), [255, 0, 255, 255]);
+        assert_eq!(pixel(&fallback, 4, 0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&fallback, 3, 4), [0, 0, 0, 255]);
+        assert_eq!(pixel(&fallback, 7, 7), [255, 0, 255, 255]);
+    }
+
+    #[test]
+    fn missing_files_load_the_fallback() {
+        let texture = Texture::from_path_or_fallback(
+            Device::Headless,
+            "assets/missing.png",
+            &TextureSpecification::DEFAULT,
+        );
+        let fallback = Texture::fallback(Device::Headless);
+        assert_eq!(*texture.pixels().unwrap(), *fallback.pixels().unwrap());
+    }
+
+    #[test]
+    fn decode_errors_name_the_file() {
+        let error = Texture::try_from_path(
+            Device::Headless,
+            "assets/missing.png",
+            &TextureSpecification::DEFAULT,
+        );
+        let Err(TextureError::Decode(message)) = error else {
+            panic!("Expected a decode error");
+        };
+        assert!(message.starts_with("assets/missing.png: "), "{}", message);
+    }
+
+    #[test]
+    fn with_sampler_shares_the_pixels() {
+        let texture = Texture::new(Device::Headless, 2, 1, TextureFormat::R8g8b8a8Unorm);
+        let smooth = texture.with_sampler(SamplerDescription::LINEAR);
+        assert_eq!(smooth.id, texture.id);
+        assert_eq!(smooth.sampler_description(), SamplerDescription::LINEAR);
+        assert_eq!(texture.sampler_description(), SamplerDescription::DEFAULT);
+        // Batch draws them separately
+        assert!(smooth != texture);
+        assert!(texture.with_sampler(SamplerDescription::DEFAULT) == texture);
+
+        texture.write_pixels(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(*smooth.pixels().unwrap(), [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn mip_chains_end_at_1x1() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(8, 8), 4);
+        assert_eq!(mip_level_count(320, 180), 9);
+        assert_eq!(mip_level_count(1, 256), 9);
+    }
+}
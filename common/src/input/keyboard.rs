@@ -46,6 +46,38 @@ impl Keyboard {
     pub fn clear_pressed(&mut self) {
         self.pressed_keys.clear();
     }
+
+    /**
+     * Appends the key state to `out`, used to record input.
+     * [pressed count: u16][pressed keys: i32..][held count: u16][held keys: i32..]
+     */
+    pub fn write_snapshot(&self, out: &mut Vec<u8>) {
+        for keys in [&self.pressed_keys, &self.held_keys] {
+            out.extend_from_slice(&(keys.len() as u16).to_le_bytes());
+            for key in keys {
+                out.extend_from_slice(&(*key as i32).to_le_bytes());
+            }
+        }
+    }
+
+    /**
+     * Replaces the key state with one written by `write_snapshot`.
+     * Returns the number of bytes read, or None if `bytes` is malformed.
+     */
+    pub fn read_snapshot(&mut self, bytes: &[u8]) -> Option<usize> {
+        let mut offset = 0;
+        for keys in [&mut self.pressed_keys, &mut self.held_keys] {
+            keys.clear();
+            let count = u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?);
+            offset += 2;
+            for _ in 0..count {
+                let code = i32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?);
+                offset += 4;
+                keys.insert(Keycode::from_i32(code)?);
+            }
+        }
+        Some(offset)
+    }
 }
 
 pub static mut KEYBOARD: *const Keyboard = std::ptr::null_mut();
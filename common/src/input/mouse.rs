@@ -153,6 +153,49 @@ impl Mouse {
         self.wheel.x = 0f32;
         self.wheel.y = 0f32;
     }
+
+    pub const SNAPSHOT_SIZE: usize = 6 * 4 + 5;
+
+    /**
+     * Appends the mouse state to `out` (Mouse::SNAPSHOT_SIZE bytes), used to record input.
+     */
+    pub fn write_snapshot(&self, out: &mut Vec<u8>) {
+        for value in [
+            self.position.x,
+            self.position.y,
+            self.wheel.x,
+            self.wheel.y,
+            self.position_delta.x,
+            self.position_delta.y,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out.push(self.left.get() as u8);
+        out.push(self.right.get() as u8);
+        out.push(self.right_held.get() as u8);
+        out.push(self.left_history.get());
+        out.push(self.left_consumed.get() as u8);
+    }
+
+    /**
+     * Replaces the mouse state with one written by `write_snapshot`.
+     * Returns the number of bytes read, or None if `bytes` is too short.
+     */
+    pub fn read_snapshot(&mut self, bytes: &[u8]) -> Option<usize> {
+        let bytes = bytes.get(..Self::SNAPSHOT_SIZE)?;
+        let float = |index: usize| {
+            f32::from_le_bytes(bytes[index * 4..index * 4 + 4].try_into().unwrap())
+        };
+        self.position = glm::vec2(float(0), float(1));
+        self.wheel = glm::vec2(float(2), float(3));
+        self.position_delta = glm::vec2(float(4), float(5));
+        self.left.set(bytes[24] != 0);
+        self.right.set(bytes[25] != 0);
+        self.right_held.set(bytes[26] != 0);
+        self.left_history.set(bytes[27]);
+        self.left_consumed.set(bytes[28] != 0);
+        Some(Self::SNAPSHOT_SIZE)
+    }
 }
 
 pub static mut MOUSE: *const Mouse = std::ptr::null_mut();
@@ -2,11 +2,16 @@ pub mod game_memory;
 pub mod graphics;
 pub mod input;
 pub mod memory;
+pub mod time;
 pub mod ui;
 pub mod utils;
 
-pub use sdl3::gpu::Device;
+pub use graphics::device::Device;
 pub use sdl3::gpu::TextureFormat;
+pub use sdl3::gpu::{
+    CompareOp, CullMode, Filter, FrontFace, PrimitiveType, SampleCount, SamplerAddressMode,
+    SamplerMipmapMode, StencilOp,
+};
 pub use sdl3::iostream::IOStream;
 pub use sdl3::keyboard::Keycode;
 pub use sdl3::rect::Point;
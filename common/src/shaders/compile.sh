@@ -15,6 +15,8 @@ compile() {
 SCRIPT_DIR=$( cd -- "$( dirname -- "${BASH_SOURCE[0]}" )" &> /dev/null && pwd )
 
 for file in $SCRIPT_DIR/*.hlsl; do
-	compile "$file" vertex $SCRIPT_DIR/Compiled
-	compile "$file" fragment $SCRIPT_DIR/Compiled
+	compile "$file" vertex $SCRIPT_DIR/compiled
+	compile "$file" fragment $SCRIPT_DIR/compiled
+	# What common/build.rs checks the outputs against
+	cp "$file" $SCRIPT_DIR/compiled/
 done
\ No newline at end of file
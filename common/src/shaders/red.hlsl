@@ -7,6 +7,12 @@ cbuffer UniformBlock : register(b0, space1)
     float4x4 Matrix;
 };
 
+cbuffer FragmentUniforms : register(b0, space3)
+{
+    float4 Tint; // Pulses towards Tint.rgb, Tint.a is the strength
+    float Time;
+};
+
 struct VsInput
 {
     float3 Position : TEXCOORD0;
@@ -41,5 +47,7 @@ float4 fragment_main(VsOutput input) : SV_Target0
     float wash = input.mult_wash_fill_pad.y;
     float fill = input.mult_wash_fill_pad.z;
     float4 output =  mult * texture * color + wash * texture.a * color + fill * color;
-    return float4(output.x, output.y * 0.2, output.z * 0.2, 1.0);
+    float4 red = float4(output.x, output.y * 0.2, output.z * 0.2, 1.0);
+    float pulse = 0.5 + 0.5 * sin(Time * 6.0);
+    return lerp(red, Tint, Tint.a * pulse);
 }
\ No newline at end of file
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+/**
+ * Simulation clock, owned by the runtime and read by the game through static methods.
+ *
+ * Updates run at a fixed timestep: `delta` never changes between updates, `total` only
+ * advances when an update runs. Rendering happens in between and uses `alpha` to blend the
+ * previous update with the latest one.
+ */
+#[derive(Debug, Clone)]
+pub struct Time {
+    delta: f32,
+    total: f64,
+    alpha: f32,
+    updates: u64,
+    dropped_frames: u64,
+}
+
+impl Time {
+    fn get() -> &'static Time {
+        unsafe { &*TIME }
+    }
+
+    // ---- Static Methods meant to be invoked by the game dll
+
+    /**
+     * Seconds simulated by one update (the fixed timestep).
+     */
+    pub fn delta() -> f32 {
+        Self::get().delta
+    }
+
+    /**
+     * Seconds simulated since the game started.
+     */
+    pub fn total() -> f64 {
+        Self::get().total
+    }
+
+    /**
+     * How far (0..1) the frame being rendered is between the previous update and the latest one.
+     */
+    pub fn alpha() -> f32 {
+        Self::get().alpha
+    }
+
+    pub fn updates() -> u64 {
+        Self::get().updates
+    }
+
+    /**
+     * Frames that could not be rendered because updates had to catch up.
+     */
+    pub fn dropped_frames() -> u64 {
+        Self::get().dropped_frames
+    }
+
+    // ---- Methods below are meant to be invoked by the runtime
+
+    pub fn new(timestep: Duration) -> Self {
+        Time {
+            delta: timestep.as_secs_f32(),
+            total: 0f64,
+            alpha: 0f32,
+            updates: 0,
+            dropped_frames: 0,
+        }
+    }
+
+    pub fn advance(&mut self) {
+        self.total += self.delta as f64;
+        self.updates += 1;
+    }
+
+    pub fn set_alpha(&mut self, alpha: f32) {
+        self.alpha = alpha.clamp(0f32, 1f32);
+    }
+
+    pub fn drop_frames(&mut self, count: u64) {
+        self.dropped_frames += count;
+    }
+}
+
+pub static mut TIME: *const Time = std::ptr::null_mut();
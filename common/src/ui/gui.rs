@@ -1,13 +1,11 @@
-use sdl3::gpu::Device;
-
 use crate::{
-    graphics::{batch::Batch, texture::Texture},
+    graphics::{batch::Batch, device::Device, subtexture::Subtexture, texture::Texture},
     ui::window::Window,
     utils::{font_atlas::FontAtlas, glyph::GlyphData},
 };
 
 const MAX_WINDOWS: usize = 8;
-static FONT_ATLAS: &[u8] =
+pub static FONT_ATLAS: &[u8] =
     include_bytes!("/Users/feresr/Workspace/learn_sdl3_gpu/common/src/ui/Iosevka/Iosevka.png");
 static FONT_GLYPH_DATA_FNT: &'static str =
     include_str!("/Users/feresr/Workspace/learn_sdl3_gpu/common/src/ui/Iosevka/Iosevka.txt");
@@ -22,8 +20,15 @@ pub struct Gui {
 impl Gui {
     pub fn new(device: Device) -> Self {
         let texture_atlas = Texture::from_bytes(device, FONT_ATLAS);
+        return Gui::with_font(Subtexture::whole(texture_atlas));
+    }
+
+    /**
+     * `font` is FONT_ATLAS, e.g. packed with the game's textures so the GUI shares their draws.
+     */
+    pub fn with_font(font: Subtexture) -> Self {
         let glyph_data = GlyphData::from_fnt_file(FONT_GLYPH_DATA_FNT);
-        let atlas = FontAtlas::new(texture_atlas, glyph_data);
+        let atlas = FontAtlas::new(font, glyph_data);
         Self {
             windows: Default::default(),
             window_count: Default::default(),
@@ -1,4 +1,4 @@
-use sdl3::render::FRect;
+use sdl3::{rect::Rect, render::FRect};
 
 const DEBUG_LAYOUT: bool = false;
 
@@ -15,6 +15,8 @@ use crate::{
 const MAX_WIDGETS: usize = 26;
 pub(crate) const PADDING: f32 = 12f32;
 pub(crate) const HEADER_HEIGHT: f32 = 32f32;
+const SCROLL_SPEED: f32 = 24f32;
+const SCROLLBAR_WIDTH: f32 = 4f32;
 
 #[derive(Default)]
 pub struct Window {
@@ -30,6 +32,10 @@ pub struct Window {
     widget_count: usize,
     direction: Direction,
     expanded: bool,
+    // Height of all the widgets, self.size.y is capped to max_height
+    content_height: f32,
+    max_height: Option<f32>,
+    scroll: f32,
 }
 
 impl Window {
@@ -64,6 +70,9 @@ impl Window {
         }
 
         if self.is_hovering_window(Mouse::position()) && self.expanded {
+            // Sizes are still the ones from the previous frame (clear() runs after update)
+            self.scroll -= Mouse::wheel().y * SCROLL_SPEED;
+            self.clamp_scroll();
             if Mouse::left_clicked() {
                 focused = true;
                 let mouse_position = Mouse::position_relative(self.position);
@@ -87,6 +96,20 @@ impl Window {
         self.cursor.y = HEADER_HEIGHT + PADDING;
         self.size.x = 0f32;
         self.size.y = 0f32;
+        self.content_height = 0f32;
+    }
+
+    /**
+     * Caps the window height (header included), taller content scrolls with the mouse wheel.
+     * None (the default) grows the window to fit its widgets.
+     */
+    pub fn set_max_height(&mut self, max_height: Option<f32>) {
+        self.max_height = max_height;
+    }
+
+    fn clamp_scroll(&mut self) {
+        let overflow = (self.content_height - self.size.y).max(0f32);
+        self.scroll = self.scroll.clamp(0f32, overflow);
     }
 
     /**
@@ -103,7 +126,11 @@ impl Window {
 
         // Grow the window to accomodate the new element if needed.
         self.size.x = (self.cursor.x + w + PADDING).max(self.size.x);
-        self.size.y = (self.cursor.y + h + PADDING).max(self.size.y);
+        self.content_height = (self.cursor.y + h + PADDING).max(self.content_height);
+        self.size.y = match self.max_height {
+            Some(max_height) => self.content_height.min(max_height),
+            None => self.content_height,
+        };
 
         // Then calculate where the next widget should be placed (move cursor)
         // take into account direction and this widget size
@@ -119,7 +146,11 @@ impl Window {
 
         // Check wether this widget was clicked
         let mut clicked = false;
-        if let Some((click_x, click_y)) = self.click {
+        if let Some((click_x, click_y)) = self.click
+            && click_y >= HEADER_HEIGHT
+        {
+            // Widgets are laid out unscrolled, the click is on screen
+            let click_y = click_y + self.scroll;
             clicked = click_x > x && click_x < x + w && click_y >= y && click_y <= y + h;
         }
 
@@ -161,27 +192,32 @@ impl Window {
         // TODO: Investigate SDL text rendering capabilities instead of custom impl?
         self.draw_text(self.title, glm::vec2(PADDING, 6f32), batch, atlas);
 
-        if !self.expanded {
+        if !self.expanded || self.size.y <= HEADER_HEIGHT {
             return;
         }
+
+        // Widgets outside the body (below the header) are clipped, and scrolled if they overflow
+        self.clamp_scroll();
+        batch.push_scissor(Rect::new(
+            self.position.x as i32,
+            (self.position.y + HEADER_HEIGHT) as i32,
+            self.size.x as u32,
+            (self.size.y - HEADER_HEIGHT) as u32,
+        ));
+
         // Draw the rest of the widgets
         // TODO: move draw into each Widget?
         for widget_index in 0..self.widget_count {
             let widget = &self.widgets[widget_index];
+            let widget_position = glm::vec2(widget.rect.x, widget.rect.y - self.scroll);
             match &widget.widget {
-                Widget::Text(str) => self.draw_text(
-                    &str.clone(),
-                    glm::vec2(widget.rect.x, widget.rect.y),
-                    batch,
-                    atlas,
-                ),
+                Widget::Text(str) => self.draw_text(&str.clone(), widget_position, batch, atlas),
                 Widget::Button(str, color) => {
                     const BUTTON_COLOR_HOVER: [u8; 4] = [14, 14, 14, 255];
                     const BUTTON_COLOR_CLICK: [u8; 4] = [24, 24, 24, 255];
                     // TODO: fix button hovering (it will highlight when overing outside the button - right)
-                    let mouse_rel_position = Mouse::position_relative(
-                        self.position + glm::vec2(widget.rect.x, widget.rect.y),
-                    );
+                    let mouse_rel_position =
+                        Mouse::position_relative(self.position + widget_position);
                     let button_color = if mouse_rel_position.x >= 0f32
                         && mouse_rel_position.x <= self.size.x - PADDING * 2f32
                         && mouse_rel_position.y >= 0f32
@@ -199,8 +235,8 @@ impl Window {
                     // Draw button background
                     batch.rect(
                         [
-                            self.position.x + widget.rect.x,
-                            self.position.y + widget.rect.y,
+                            self.position.x + widget_position.x,
+                            self.position.y + widget_position.y,
                             0f32,
                         ],
                         // [self.size.x - PADDING * 2f32, BUTTON_HEIGHT],
@@ -210,7 +246,7 @@ impl Window {
 
                     // Draw button label (centered)
                     let label: String = str.to_string();
-                    let mut label_position = glm::vec2(widget.rect.x, widget.rect.y);
+                    let mut label_position = widget_position;
                     let widget_w = widget.rect.w;
                     let (w, h) = self.measure_text(&label, atlas);
                     label_position.x += (widget_w) / 2f32 - w / 2f32;
@@ -218,16 +254,10 @@ impl Window {
                     self.draw_text(&label, label_position, batch, atlas);
                 }
                 Widget::Texture(texture) => {
-                    batch.texture(
-                        texture.clone(),
-                        &(self.position + glm::vec2(widget.rect.x, widget.rect.y)),
-                    );
+                    batch.texture(texture.clone(), &(self.position + widget_position));
                 }
                 Widget::Subtexture(subtexture) => {
-                    batch.subtexture(
-                        subtexture.clone(),
-                        self.position + glm::vec2(widget.rect.x, widget.rect.y),
-                    );
+                    batch.subtexture(subtexture.clone(), self.position + widget_position);
                 }
                 Widget::None => {}
             }
@@ -236,8 +266,8 @@ impl Window {
                 let widget = &self.widgets[widget_index];
                 batch.rect(
                     [
-                        self.position.x + widget.rect.x,
-                        self.position.y + widget.rect.y,
+                        self.position.x + widget_position.x,
+                        self.position.y + widget_position.y,
                         0f32,
                     ],
                     // [self.size.x - PADDING * 2f32, BUTTON_HEIGHT],
@@ -246,6 +276,28 @@ impl Window {
                 );
             }
         }
+
+        // Scrollbar, only when the content overflows
+        if self.content_height > self.size.y {
+            const SCROLLBAR_COLOR: [u8; 4] = [90, 90, 110, 255];
+            let body_height = self.size.y - HEADER_HEIGHT;
+            let content_body_height = self.content_height - HEADER_HEIGHT;
+            batch.rect(
+                [
+                    self.position.x + self.size.x - SCROLLBAR_WIDTH,
+                    self.position.y
+                        + HEADER_HEIGHT
+                        + self.scroll * body_height / content_body_height,
+                    0f32,
+                ],
+                [
+                    SCROLLBAR_WIDTH,
+                    body_height * body_height / content_body_height,
+                ],
+                SCROLLBAR_COLOR,
+            );
+        }
+        batch.pop_scissor();
     }
 
     pub fn set_direction(&mut self, direction: Direction) {
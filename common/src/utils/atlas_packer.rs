@@ -0,0 +1,248 @@
+use sdl3::rect::Rect;
+
+use crate::graphics::{
+    device::Device,
+    pixel_format::{Image, ImagePixels},
+    subtexture::Subtexture,
+    texture::{Texture, TextureError, TextureSpecification, decode_bytes},
+};
+
+/**
+ * Packs images into as few textures (pages) as possible, so that everything drawn from them
+ * ends up in the same Batch draw:
+ *
+ *   let mut packer = AtlasPacker::new(256, 256);
+ *   let tiles = packer.add_bytes(TILES_PNG)?;
+ *   let player = packer.add_bytes(PLAYER_PNG)?;
+ *   let atlas = packer.pack(device);
+ *   let tile_atlas = TileAtlas::new(atlas.get(tiles), 8);
+ *
+ * Shelf packing: images are sorted by height and laid out in rows, a page is added when one
+ * is full. `padding` transparent pixels separate images, so filtering doesn't bleed across them.
+ */
+pub struct AtlasPacker {
+    page_width: u32,
+    page_height: u32,
+    pub padding: u32,
+    images: Vec<Image>,
+}
+
+/**
+ * Id of an image added to an AtlasPacker, look it up in the PackedAtlas.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasEntry(usize);
+
+pub struct PackedAtlas {
+    pub pages: Vec<Texture>,
+    // By AtlasEntry
+    regions: Vec<Subtexture>,
+}
+
+impl AtlasPacker {
+    pub fn new(page_width: u32, page_height: u32) -> Self {
+        AtlasPacker {
+            page_width,
+            page_height,
+            padding: 1,
+            images: Vec::new(),
+        }
+    }
+
+    /**
+     * Any image Texture::try_from_bytes reads, converted to RGBA8.
+     */
+    pub fn add_bytes(&mut self, bytes: &[u8]) -> Result<AtlasEntry, TextureError> {
+        let image = decode_bytes(bytes)?;
+        let pixels = image
+            .convert(TextureSpecification::DEFAULT.format)
+            .expect("RGBA8 is always supported");
+        return Ok(self.add_pixels(image.width, image.height, pixels));
+    }
+
+    /**
+     * `pixels` are RGBA8 rows.
+     */
+    pub fn add_pixels(&mut self, width: u32, height: u32, pixels: Vec<u8>) -> AtlasEntry {
+        assert_eq!(
+            pixels.len(),
+            (width * height * 4) as usize,
+            "Expected {}x{} RGBA8 pixels",
+            width,
+            height
+        );
+        assert!(
+            width <= self.page_width && height <= self.page_height,
+            "A {}x{} image doesn't fit in {}x{} pages",
+            width,
+            height,
+            self.page_width,
+            self.page_height
+        );
+        self.images.push(Image {
+            width,
+            height,
+            channels: 4,
+            pixels: ImagePixels::U8(pixels),
+        });
+        return AtlasEntry(self.images.len() - 1);
+    }
+
+    pub fn pack(self, device: Device) -> PackedAtlas {
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(self.images[index].height));
+
+        // (page, rect) by AtlasEntry
+        let mut placements = vec![(0, Rect::new(0, 0, 1, 1)); self.images.len()];
+        let mut page = 0;
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for index in order {
+            let image = &self.images[index];
+            if x + image.width > self.page_width {
+                x = 0;
+                y += shelf_height + self.padding;
+                shelf_height = 0;
+            }
+            if y + image.height > self.page_height {
+                page += 1;
+                (x, y, shelf_height) = (0, 0, 0);
+            }
+            placements[index] = (
+                page,
+                Rect::new(x as i32, y as i32, image.width, image.height),
+            );
+            x += image.width + self.padding;
+            shelf_height = shelf_height.max(image.height);
+        }
+
+        let page_count = if self.images.is_empty() { 0 } else { page + 1 };
+        let row_size = (self.page_width * 4) as usize;
+        let mut page_pixels = vec![vec![0u8; row_size * self.page_height as usize]; page_count];
+        for (image, (page, rect)) in self.images.iter().zip(&placements) {
+            let ImagePixels::U8(pixels) = &image.pixels else {
+                unreachable!("Images are added as RGBA8");
+            };
+            let image_row_size = (image.width * 4) as usize;
+            for (row, source) in pixels.chunks_exact(image_row_size).enumerate() {
+                let start = (rect.y as usize + row) * row_size + rect.x as usize * 4;
+                page_pixels[*page][start..start + image_row_size].copy_from_slice(source);
+            }
+        }
+
+        let pages: Vec<Texture> = page_pixels
+            .into_iter()
+            .map(|pixels| {
+                let texture = Texture::from_specification(
+                    device.clone(),
+                    self.page_width as u16,
+                    self.page_height as u16,
+                    &TextureSpecification::DEFAULT,
+                );
+                texture.write_pixels(&pixels);
+                texture
+            })
+            .collect();
+        let regions = placements
+            .into_iter()
+            .map(|(page, rect)| Subtexture::new(pages[page].clone(), rect))
+            .collect();
+        return PackedAtlas { pages, regions };
+    }
+}
+
+impl PackedAtlas {
+    pub fn get(&self, entry: AtlasEntry) -> Subtexture {
+        return self.regions[entry.0].clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> Vec<u8> {
+        return color.repeat((width * height) as usize);
+    }
+
+    // (page, x, y) of each entry
+    fn placements(atlas: &PackedAtlas, entries: &[AtlasEntry]) -> Vec<(usize, i32, i32)> {
+        entries
+            .iter()
+            .map(|entry| {
+                let region = atlas.get(*entry);
+                let page = atlas
+                    .pages
+                    .iter()
+                    .position(|page| page.id == region.texture.id)
+                    .unwrap();
+                (page, region.rect.x, region.rect.y)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lays_images_out_in_shelves_by_height() {
+        let mut packer = AtlasPacker::new(10, 16);
+        let short = packer.add_pixels(4, 2, solid(4, 2, [255; 4]));
+        let tall = packer.add_pixels(4, 6, solid(4, 6, [255; 4]));
+        let medium = packer.add_pixels(4, 4, solid(4, 4, [255; 4]));
+        let atlas = packer.pack(Device::Headless);
+
+        assert_eq!(atlas.pages.len(), 1);
+        // The shortest doesn't fit next to the others, it starts a shelf below the tallest
+        assert_eq!(
+            placements(&atlas, &[tall, medium, short]),
+            [(0, 0, 0), (0, 5, 0), (0, 0, 7)]
+        );
+        assert_eq!(atlas.get(short).rect, Rect::new(0, 7, 4, 2));
+    }
+
+    #[test]
+    fn adds_a_page_when_one_is_full() {
+        let mut packer = AtlasPacker::new(8, 8);
+        let entries: Vec<AtlasEntry> = (0..4)
+            .map(|_| packer.add_pixels(3, 4, solid(3, 4, [255; 4])))
+            .collect();
+        let atlas = packer.pack(Device::Headless);
+
+        assert_eq!(atlas.pages.len(), 2);
+        // With the padding, a second shelf would need 9 rows
+        assert_eq!(
+            placements(&atlas, &entries),
+            [(0, 0, 0), (0, 4, 0), (1, 0, 0), (1, 4, 0)]
+        );
+    }
+
+    #[test]
+    fn separates_images_with_transparent_padding() {
+        let red = [255, 0, 0, 255];
+        let green = [0, 255, 0, 255];
+        let mut packer = AtlasPacker::new(4, 1);
+        packer.padding = 2;
+        let first = packer.add_pixels(1, 1, red.to_vec());
+        let second = packer.add_pixels(1, 1, green.to_vec());
+        let atlas = packer.pack(Device::Headless);
+
+        assert_eq!(placements(&atlas, &[first, second]), [(0, 0, 0), (0, 3, 0)]);
+        assert_eq!(
+            *atlas.pages[0].pixels().unwrap(),
+            [red, [0; 4], [0; 4], green].concat()
+        );
+    }
+
+    #[test]
+    fn packs_nothing_into_no_pages() {
+        assert!(
+            AtlasPacker::new(8, 8)
+                .pack(Device::Headless)
+                .pages
+                .is_empty()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "A 9x1 image doesn't fit in 8x8 pages")]
+    fn rejects_images_larger_than_a_page() {
+        AtlasPacker::new(8, 8).add_pixels(9, 1, solid(9, 1, [255; 4]));
+    }
+}
@@ -1,10 +1,15 @@
-use std::ops::{Add, Sub};
+use std::{
+    hash::Hasher,
+    ops::{Add, Sub},
+};
 
 use crate::graphics::IDENTITY;
 
 pub mod animation;
+pub mod atlas_packer;
 pub mod font_atlas;
 pub mod glyph;
+pub mod png;
 pub mod tile_atlas;
 
 pub fn approach<T>(current: T, value: T, step: T) -> T
@@ -31,4 +36,56 @@ pub fn create_transform(position: glm::Vec2, origin: glm::Vec2, scale: glm::Vec2
     return glm::translate(&IDENTITY, &glm::vec3(position.x, position.y, 0.0f32))
         * glm::scale(&IDENTITY, &glm::vec3(scale.x, scale.y, 1.0f32))
         * glm::translate(&IDENTITY, &glm::vec3(-origin.x, -origin.y, 0.0f32));
-}
\ No newline at end of file
+}
+
+/**
+ * FNV-1a, stable across runs and platforms (unlike std's DefaultHasher): for checksums and
+ * hashes that end up in files, e.g. recordings and golden files.
+ * Feed it little-endian bytes, Hasher's write_u32 & co. use the native order.
+ */
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::default();
+    hasher.write(bytes);
+    return hasher.finish();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_the_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn fnv1a_hashes_a_stream_like_its_concatenation() {
+        let mut hasher = Fnv1a::default();
+        hasher.write(b"foo");
+        hasher.write(b"bar");
+        assert_eq!(hasher.finish(), fnv1a(b"foobar"));
+    }
+}
@@ -0,0 +1,189 @@
+use std::{io, path::Path};
+
+/**
+ * Minimal PNG encoder for RGBA8 images (screenshots, CPU render targets).
+ *
+ * The image data is zlib-wrapped but stored uncompressed: files are larger than needed,
+ * in exchange there's no deflate implementation to maintain. Any PNG decoder reads them.
+ */
+pub fn encode_rgba(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    assert_eq!(
+        pixels.len(),
+        (width * height * 4) as usize,
+        "Expected {}x{} RGBA8 pixels",
+        width,
+        height
+    );
+
+    // Every row starts with its filter type (0: None)
+    let row_size = width as usize * 4;
+    let mut raw = Vec::with_capacity((row_size + 1) * height as usize);
+    for row in pixels.chunks_exact(row_size) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[
+        8, // bit depth
+        6, // color type: RGBA
+        0, // compression: deflate
+        0, // filter method
+        0, // no interlace
+    ]);
+
+    let mut png = Vec::with_capacity(raw.len() + raw.len() / 65535 * 5 + 64);
+    png.extend_from_slice(b"\x89PNG\r\n\x1a\n");
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    return png;
+}
+
+pub fn write_rgba<P: AsRef<Path>>(
+    path: P,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> io::Result<()> {
+    std::fs::write(path, encode_rgba(width, height, pixels))
+}
+
+/**
+ * Any PNG (not only ours) as RGBA8: (width, height, pixels).
+ */
+pub fn read_rgba<P: AsRef<Path>>(path: P) -> Option<(u32, u32, Vec<u8>)> {
+    match stb_image::image::load_with_depth(path, 4, false) {
+        stb_image::image::LoadResult::ImageU8(image) => {
+            Some((image.width as u32, image.height as u32, image.data))
+        }
+        _ => None,
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let crc_start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[crc_start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// zlib stream made of uncompressed deflate blocks (at most 65535 bytes each)
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 65535 * 5 + 11);
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut blocks = data.chunks(65535).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]); // Single empty final block
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(is_final as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    return out;
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    return !crc;
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest n such that the sums can't overflow before the modulo
+    for chunk in bytes.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    return (b << 16) | a;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_the_reference_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    }
+
+    #[test]
+    fn adler32_matches_the_reference_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        // Long enough to need several modulo rounds
+        let bytes = vec![0xffu8; 100_000];
+        let (mut a, mut b) = (1u64, 0u64);
+        for byte in &bytes {
+            a = (a + *byte as u64) % 65521;
+            b = (b + a) % 65521;
+        }
+        assert_eq!(adler32(&bytes), ((b << 16) | a) as u32);
+    }
+
+    #[test]
+    fn zlib_stored_splits_into_65535_byte_blocks() {
+        assert_eq!(
+            zlib_stored(&[]),
+            [0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]
+        );
+
+        let data: Vec<u8> = (0..70_000u32).map(|i| i as u8).collect();
+        let stream = zlib_stored(&data);
+        assert_eq!(stream[..2], [0x78, 0x01]);
+        let mut offset = 2;
+        let mut inflated = Vec::new();
+        let mut finals = Vec::new();
+        while offset < stream.len() - 4 {
+            let len = u16::from_le_bytes([stream[offset + 1], stream[offset + 2]]);
+            let nlen = u16::from_le_bytes([stream[offset + 3], stream[offset + 4]]);
+            assert_eq!(nlen, !len);
+            finals.push(stream[offset]);
+            inflated.extend_from_slice(&stream[offset + 5..][..len as usize]);
+            offset += 5 + len as usize;
+        }
+        assert_eq!(finals, [0, 1]);
+        assert_eq!(inflated, data);
+        assert_eq!(stream[offset..], adler32(&data).to_be_bytes());
+    }
+
+    #[test]
+    fn decoders_read_back_the_encoded_pixels() {
+        let (width, height) = (3, 2);
+        let pixels: Vec<u8> = (0..width * height * 4).map(|i| (i * 7) as u8).collect();
+        let png = encode_rgba(width, height, &pixels);
+        assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+
+        match stb_image::image::load_from_memory_with_depth(&png, 4, false) {
+            stb_image::image::LoadResult::ImageU8(image) => {
+                assert_eq!((image.width, image.height), (3, 2));
+                assert_eq!(image.data, pixels);
+            }
+            _ => panic!("stb_image could not decode our PNG"),
+        }
+    }
+}
@@ -1,23 +1,24 @@
 use sdl3::rect::Rect;
 
-use crate::graphics::{subtexture::Subtexture, texture::Texture};
+use crate::graphics::subtexture::Subtexture;
 
 pub struct TileAtlas {
-    texture: Texture,
+    // The whole texture, or its region in a packed atlas (see AtlasPacker)
+    texture: Subtexture,
     tiles_width: u16,
     tiles_height: u16,
     tile_size: u16,
 }
 
 impl TileAtlas {
-    pub fn new(texture: Texture, tile_size: u16) -> Self {
+    pub fn new(texture: Subtexture, tile_size: u16) -> Self {
         assert!(
             // TODO: u32 as u16 might overflow
-            (texture.width() as u16) % tile_size == 0 && (texture.height() as u16) % tile_size == 0,
+            (texture.rect.w as u16) % tile_size == 0 && (texture.rect.h as u16) % tile_size == 0,
             "Texture dimensions must be divisible by tile_size"
         );
-        let tiles_width = texture.width() as u16 / tile_size;
-        let tiles_height = texture.height() as u16 / tile_size;
+        let tiles_width = texture.rect.w as u16 / tile_size;
+        let tiles_height = texture.rect.h as u16 / tile_size;
         Self {
             texture,
             tiles_width,
@@ -36,7 +37,7 @@ impl TileAtlas {
             self.tile_size as u32,
         );
 
-        Subtexture::new(self.texture.clone(), rect)
+        self.texture.region(rect)
     }
 
     pub fn get(&self, x: u16, y: u16) -> Subtexture {
@@ -47,7 +48,7 @@ impl TileAtlas {
             self.tile_size as u32,
         );
 
-        Subtexture::new(self.texture.clone(), rect)
+        self.texture.region(rect)
     }
 }
 
@@ -1,13 +1,14 @@
 use common::{
-    FPoint, FRect,
-    graphics::IDENTITY,
+    Device, FPoint, FRect, Rect, TextureFormat,
+    graphics::{IDENTITY, texture::Texture},
     input::{keyboard::Keyboard, mouse::Mouse},
     ui::{gui::Gui, utils::Direction, widget::Widget},
     utils::{create_transform_inplace, tile_atlas::TileAtlas},
 };
 
 use crate::{
-    room::{ROOM_HEIGHT, ROOM_WIDTH, TILE_SIZE},
+    room::{COLUMNS, ROOM_HEIGHT, ROOM_WIDTH, ROWS, TILE_SIZE},
+    snapshot::{Reader, Writer},
     world::{World, WORLD_COLUMNS, WORLD_ROWS},
 };
 
@@ -26,10 +27,17 @@ pub struct Editor {
     projection: glm::Mat4,
     layer: Layer,
     mouse_cursor: FRect,
+    // One pixel per tile of the whole world, repainted one pixel at a time as tiles change
+    minimap: Texture,
+    // False until the whole world has been written into the minimap
+    minimap_ready: bool,
 }
 
+const MINIMAP_WIDTH: usize = WORLD_COLUMNS * COLUMNS;
+const MINIMAP_HEIGHT: usize = WORLD_ROWS * ROWS;
+
 impl Editor {
-    pub fn default() -> Editor {
+    pub fn new(device: Device) -> Editor {
         Self {
             is_showing: false,
             selected_tile: 0,
@@ -43,10 +51,20 @@ impl Editor {
                 w: TILE_SIZE as f32,
                 h: TILE_SIZE as f32,
             },
+            minimap: Texture::new(
+                device,
+                MINIMAP_WIDTH as u16,
+                MINIMAP_HEIGHT as u16,
+                TextureFormat::R8g8b8a8Unorm,
+            ),
+            minimap_ready: false,
         }
     }
 
     pub fn update(&mut self, world: &mut World, atlas: &TileAtlas) {
+        if !self.minimap_ready {
+            self.refresh_minimap(world);
+        }
         self.draw_editor_controls(world, atlas);
 
         if Keyboard::pressed(common::Keycode::_1) {
@@ -105,9 +123,58 @@ impl Editor {
             let tile = tiles.get_cell_at_position_mut(room_local_x, room_local_y);
             tile.id = self.selected_tile as u8;
             tile.visible = true;
+
+            // Only this tile's pixel gets uploaded
+            let tile_x = room_index_x * COLUMNS + room_local_x / TILE_SIZE;
+            let tile_y = room_index_y * ROWS + room_local_y / TILE_SIZE;
+            self.minimap.write_region(
+                Rect::new(tile_x as i32, tile_y as i32, 1, 1),
+                &minimap_color(world, tile_x, tile_y),
+            );
         }
     }
 
+    fn refresh_minimap(&mut self, world: &World) {
+        let mut pixels = Vec::with_capacity(MINIMAP_WIDTH * MINIMAP_HEIGHT * 4);
+        for tile_y in 0..MINIMAP_HEIGHT {
+            for tile_x in 0..MINIMAP_WIDTH {
+                pixels.extend_from_slice(&minimap_color(world, tile_x, tile_y));
+            }
+        }
+        self.minimap.write_pixels(&pixels);
+        self.minimap_ready = true;
+    }
+
+    pub(crate) fn write_snapshot(&self, writer: &mut Writer) {
+        writer.bool(self.is_showing);
+        writer.u16(self.selected_tile);
+        writer.f32(self.offset.x);
+        writer.f32(self.offset.y);
+        writer.f32(self.zoom);
+        writer.bool(matches!(self.layer, Layer::Foreground));
+    }
+
+    pub(crate) fn read_snapshot(&mut self, reader: &mut Reader) -> Option<()> {
+        self.is_showing = reader.bool()?;
+        self.selected_tile = reader.u16()?;
+        self.offset = FPoint::new(reader.f32()?, reader.f32()?);
+        self.zoom = reader.f32()?;
+        self.layer = if reader.bool()? {
+            Layer::Foreground
+        } else {
+            Layer::Background
+        };
+        // The world may have been restored too
+        self.minimap_ready = false;
+        create_transform_inplace(
+            &mut self.projection,
+            glm::vec2(self.offset.x, self.offset.y),
+            glm::vec2(0.0f32, 0.0f32),
+            glm::vec2(self.zoom, self.zoom),
+        );
+        Some(())
+    }
+
     fn apply_zoom_and_pan(&mut self, new_zoom: f32) {
         let screen_space_mouse = Mouse::position();
         let zoom_ratio = new_zoom / self.zoom;
@@ -144,6 +211,8 @@ impl Editor {
     // TODO: allow removing tiles
     fn draw_editor_controls(&mut self, world: &mut World, atlas: &TileAtlas) {
         let window = Gui::window("Map Editor");
+        // The tile palette grows with the atlas, scroll through it instead
+        window.set_max_height(Some(480f32));
         let mut index = 0;
         for mut tile in atlas {
             window.set_direction(Direction::Horizontal);
@@ -188,6 +257,24 @@ impl Editor {
             }
         }
         window.add_widget(Widget::Text(format!("Drawing in {:?} layer", self.layer)));
+        window.add_widget(Widget::Texture(self.minimap.clone()));
         window.add_widget(Widget::Text("Press 1,2,3,4 to modify zoom level".to_string()));
     }
 }
+
+// Color of the tile at (tile_x, tile_y), counted in tiles from the world's top-left corner
+fn minimap_color(world: &World, tile_x: usize, tile_y: usize) -> [u8; 4] {
+    let room = world
+        .rooms
+        .get_cell_at_index(tile_x / COLUMNS, tile_y / ROWS);
+    let (local_x, local_y) = (tile_x % COLUMNS, tile_y % ROWS);
+    let foreground = room.foreground_tiles.get_cell_at_index(local_x, local_y);
+    let background = room.background_tiles.get_cell_at_index(local_x, local_y);
+    if foreground.visible {
+        return [230, 230, 230, 255];
+    }
+    if background.visible {
+        return [100, 100, 120, 255];
+    }
+    return [20, 20, 28, 255];
+}
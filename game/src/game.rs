@@ -1,26 +1,49 @@
-use crate::{SCREEN_TO_GAME_PROJECTION, camera::Camera, materials, player::Player, world::World};
+use crate::{
+    SCREEN_TO_GAME_PROJECTION,
+    camera::Camera,
+    layers,
+    materials::{self, RedUniforms},
+    player::{PLAYER_PNG, Player},
+    world::World,
+};
 use common::{
     Device, Rect, TextureFormat,
     graphics::{
-        IDENTITY, batch::Batch, material::Material, render_target::RenderTarget, texture::Texture,
+        IDENTITY, VEC_2_ZERO,
+        batch::{Batch, BatchStats},
+        material::Material,
+        post_process::{Effect, PostProcess},
+        render_target::{LoadAction, RenderTarget},
+        texture::Texture,
     },
     input::mouse::Mouse,
-    ui::gui::Gui,
-    utils::tile_atlas::TileAtlas,
+    time::Time,
+    ui::{
+        gui::{FONT_ATLAS, Gui},
+        widget::Widget,
+    },
+    utils::{atlas_packer::AtlasPacker, tile_atlas::TileAtlas},
 };
 
-static ATLAS: &[u8] =
-    include_bytes!("/Users/feresr/Workspace/learn_sdl3_gpu/game/assets/atlas.png");
+static ATLAS: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/atlas.png"));
 
 #[allow(dead_code)] // TODO: remove dead code
 pub struct Game {
     pub material: Material,
     pub game_target: RenderTarget,
+    // Run over game_target before it is scaled to the screen
+    pub post_process: PostProcess,
     pub gui: Gui,
     pub world: World,
     pub player: Player,
     pub tile_atlas: TileAtlas,
     pub camera: Camera,
+    // Of the last rendered frame, shown by the debug GUI. Written by render_game
+    pub batch_stats: BatchStats,
+    // How game_target is composited into the window, changed from the debug GUI
+    pub scaling: Scaling,
+    // Fills the window around game_target when the aspect ratios don't match
+    pub letterbox_color: [u8; 4],
 }
 
 impl Game {
@@ -32,18 +55,56 @@ impl Game {
             TextureFormat::R8g8b8a8Unorm,
         ));
 
-        let atlas_texture = Texture::from_bytes(device.clone(), ATLAS);
-        let tile_atlas = TileAtlas::new(atlas_texture, 8);
+        // Tiles, player and GUI font share one texture: fewer texture switches, fewer draws
+        let mut packer = AtlasPacker::new(256, 256);
+        let tiles = packer.add_bytes(ATLAS).expect("Could not decode atlas.png");
+        let player = packer
+            .add_bytes(PLAYER_PNG)
+            .expect("Could not decode player.png");
+        let font = packer
+            .add_bytes(FONT_ATLAS)
+            .expect("Could not decode the GUI font");
+        let packed_atlas = packer.pack(device.clone());
+        let tile_atlas = TileAtlas::new(packed_atlas.get(tiles), 8);
+
+        let mut post_process = PostProcess::new(device.clone(), 320, 180);
+        post_process.push(
+            "bloom",
+            Effect::Bloom {
+                strength: 0.6,
+                radius: 2,
+            },
+        );
+        post_process.push(
+            "color grade",
+            Effect::ColorGrade {
+                tint: [255, 236, 214, 255],
+            },
+        );
+        post_process.push("scanlines", Effect::Scanlines { strength: 0.25 });
+        post_process.push(
+            "flash",
+            Effect::Flash {
+                color: [255, 255, 255, 0],
+            },
+        );
+        for pass in post_process.passes.iter_mut() {
+            pass.enabled = pass.name == "flash";
+        }
 
         Game {
             material: Material::from_specification(device.clone(), &materials::RED_MATERIAL),
             game_target: offscreen_target,
-            gui: Gui::new(device.clone()),
+            post_process,
+            gui: Gui::with_font(packed_atlas.get(font)),
             // arena: Default::default(),
-            player: Player::new(device.clone()),
+            player: Player::new(packed_atlas.get(player)),
             world: World::from_bytes(),
             tile_atlas,
             camera: Camera::default(),
+            batch_stats: Default::default(),
+            scaling: Scaling::Integer,
+            letterbox_color: [0, 0, 0, 255],
         }
     }
 
@@ -60,11 +121,23 @@ impl Game {
             "Press 'E' to to enter the map editor.".to_string(),
         ));
         window.add_widget(common::ui::widget::Widget::Text(
-            "Press 'R' to hot-reload the game dll.".to_string(),
+            "Edit game/src (or press 'R') to hot-reload.".to_string(),
         ));
         window.add_widget(common::ui::widget::Widget::Text(
             "AWSD to move, SPACE to attack".to_string(),
         ));
+        window.add_widget(common::ui::widget::Widget::Text(
+            "F5 to record input, F6 to loop it".to_string(),
+        ));
+        window.add_widget(common::ui::widget::Widget::Text(
+            "F11 to toggle fullscreen".to_string(),
+        ));
+        window.add_widget(common::ui::widget::Widget::Text(
+            "F12 screenshot (Shift: game only), F10 capture frames".to_string(),
+        ));
+        if window.add_widget(Widget::Button(self.scaling.name(), [90, 90, 90, 255])) {
+            self.scaling = self.scaling.next();
+        }
 
         let player_position = self.player.get_position();
         // TODO extract fn to get current room logic into its own funciton (it's being invoked inside Camera too)
@@ -78,15 +151,50 @@ impl Game {
         // Follow player (it might have changed room after update(), so we need to re fetch current_room)
         self.camera
             .update(&mut self.game_target, &self.player, &self.world);
+
+        self.update_post_process();
     }
 
+    fn update_post_process(&mut self) {
+        let window = Gui::window("Post-processing");
+        window.set_direction(common::ui::utils::Direction::Vertical);
+        for pass in self.post_process.passes.iter_mut() {
+            let color = if pass.enabled {
+                [20, 132, 23, 255]
+            } else {
+                [90, 90, 90, 255]
+            };
+            if window.add_widget(Widget::Button(pass.name, color)) {
+                pass.enabled = !pass.enabled;
+            }
+        }
+
+        let trigger_flash = window.add_widget(Widget::Button("Trigger flash", [132, 90, 20, 255]));
+        if let Some(pass) = self.post_process.pass_mut("flash")
+            && let Effect::Flash { color } = &mut pass.effect
+        {
+            // Fades out over ~12 updates
+            color[3] = if trigger_flash {
+                192
+            } else {
+                color[3].saturating_sub(16)
+            };
+        }
+    }
+
+    /**
+     * Composes game_target from two passes: the room's tiles, then the entities on top.
+     */
     pub(crate) fn render(&self, batch: &mut Batch) {
-        // Draw foreground tiles (TODO: Render to an offscreen target only once - composite target)
+        // TODO: Render the tiles to an offscreen target only once per room and copy it here
         let player_position = self.player.get_position();
         let current_room = self.world.rooms.get_cell_at_position(
             player_position.x as usize,
             (player_position.y + 4) as usize, // TODO 4 is the offset between Romo size and scren size
         );
+        current_room.render(batch, &self.tile_atlas);
+        batch.draw_into(&self.game_target);
+        batch.clear();
 
         let game_mouse_position = self.game_mouse_position();
         let mut rect = Rect::new(
@@ -98,12 +206,27 @@ impl Game {
         rect.offset(-2, -2);
         let collides = current_room.collides(&rect);
 
+        batch.push_layer(layers::DEBUG);
         if collides {
+            let mut material = self.material.clone();
+            // RedUniforms is #[repr(C)] and padded by hand
+            unsafe {
+                material.set_fragment_uniform(
+                    0,
+                    &RedUniforms {
+                        tint: [1f32, 1f32, 0f32, 1f32],
+                        time: Time::total() as f32,
+                        _padding: [0f32; 3],
+                    },
+                );
+            }
+            batch.push_material(&material);
             batch.rect(
                 [rect.x as f32, rect.y as f32, 0f32],
                 [4f32, 4f32],
                 [0, 255, 0, 255],
             );
+            batch.pop_material();
         } else {
             batch.rect(
                 [rect.x as f32, rect.y as f32, 0f32],
@@ -111,10 +234,10 @@ impl Game {
                 [255, 255, 255, 255],
             );
         }
+        batch.pop_layer();
 
-        current_room.render(batch, &self.tile_atlas);
         self.player.render(batch);
-        batch.draw_into(&self.game_target);
+        batch.draw_into_with(&self.game_target, LoadAction::Load);
     }
 
     fn game_mouse_position(&self) -> glm::Vec2 {
@@ -122,24 +245,102 @@ impl Game {
     }
 }
 
+/**
+ * How game_target is scaled up to fill the window.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scaling {
+    // Largest whole multiple that fits: every game pixel is the same size on screen
+    Integer,
+    // Largest (fractional) scale that fits, keeps the aspect ratio
+    Fit,
+    // Fills the window, ignores the aspect ratio
+    Stretch,
+}
+
+impl Scaling {
+    pub fn next(self) -> Self {
+        match self {
+            Scaling::Integer => Scaling::Fit,
+            Scaling::Fit => Scaling::Stretch,
+            Scaling::Stretch => Scaling::Integer,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scaling::Integer => "Scaling: integer",
+            Scaling::Fit => "Scaling: fit",
+            Scaling::Stretch => "Scaling: stretch",
+        }
+    }
+}
+
 // TODO: move this to lib, find better place for this
 pub fn create_target_projection(
     game_target: &RenderTarget,
     screen_target: &RenderTarget,
+    scaling: Scaling,
 ) -> glm::Mat4 {
-    let scale = (screen_target.width as f32 / game_target.width as f32)
-        .min(screen_target.height as f32 / game_target.height as f32);
+    let scale_x = screen_target.width as f32 / game_target.width as f32;
+    let scale_y = screen_target.height as f32 / game_target.height as f32;
+    let fit = scale_x.min(scale_y);
+    let scale = match scaling {
+        // Windows smaller than the game target fall back to Fit
+        Scaling::Integer if fit >= 1f32 => glm::vec2(fit.floor(), fit.floor()),
+        Scaling::Integer | Scaling::Fit => glm::vec2(fit, fit),
+        Scaling::Stretch => glm::vec2(scale_x, scale_y),
+    };
 
-    let screen_center: glm::Vec2 = glm::vec2(
-        screen_target.width as f32 / 2f32,
-        screen_target.height as f32 / 2f32,
-    );
-    let game_center: glm::Vec2 = glm::vec2(
-        game_target.width as f32 / 2f32,
-        game_target.height as f32 / 2f32,
+    // Rounded so the game pixels line up with the screen pixels
+    let position = glm::vec2(
+        ((screen_target.width as f32 - game_target.width as f32 * scale.x) / 2f32).floor(),
+        ((screen_target.height as f32 - game_target.height as f32 * scale.y) / 2f32).floor(),
     );
 
-    return create_transform(screen_center, game_center, glm::vec2(scale, scale));
+    return create_transform(position, VEC_2_ZERO, scale);
+}
+
+/**
+ * Covers the parts of the screen not covered by game_target (drawn with `projection`).
+ */
+pub fn draw_letterbox(
+    batch: &mut Batch,
+    game_target: &RenderTarget,
+    screen_target: &RenderTarget,
+    projection: &glm::Mat4,
+    color: [u8; 4],
+) {
+    let top_left = projection * glm::vec4(0f32, 0f32, 0f32, 1f32);
+    let bottom_right = projection
+        * glm::vec4(
+            game_target.width as f32,
+            game_target.height as f32,
+            0f32,
+            1f32,
+        );
+    let screen_width = screen_target.width as f32;
+    let screen_height = screen_target.height as f32;
+    let game_width = bottom_right.x - top_left.x;
+
+    // Left, right, top and bottom bars
+    let bars = [
+        ([0f32, 0f32], [top_left.x, screen_height]),
+        (
+            [bottom_right.x, 0f32],
+            [screen_width - bottom_right.x, screen_height],
+        ),
+        ([top_left.x, 0f32], [game_width, top_left.y]),
+        (
+            [top_left.x, bottom_right.y],
+            [game_width, screen_height - bottom_right.y],
+        ),
+    ];
+    for (position, size) in bars {
+        if size[0] > 0f32 && size[1] > 0f32 {
+            batch.rect([position[0], position[1], 0f32], size, color);
+        }
+    }
 }
 
 // TODO: Dead code
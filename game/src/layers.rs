@@ -0,0 +1,7 @@
+use common::graphics::batch::Layer;
+
+// Draw order inside the game target, back to front. Render code can draw in any order.
+pub const BACKGROUND: Layer = Layer(-10);
+pub const FOREGROUND: Layer = Layer::DEFAULT;
+pub const PLAYER: Layer = Layer(10);
+pub const DEBUG: Layer = Layer(100);
@@ -1,11 +1,12 @@
 use common::{
     Device,
-    game_memory::GameMemory,
+    game_memory::{GAME_ABI_VERSION, GameAbi, GameMemory},
     graphics::{IDENTITY, VEC_2_ZERO, batch::Batch, render_target::RenderTarget},
     input::{
         keyboard::{KEYBOARD, Keyboard},
         mouse::{MOUSE, Mouse},
     },
+    time::{TIME, Time},
     ui::{
         gui::{GUI, Gui},
         widget::Widget,
@@ -14,15 +15,17 @@ use common::{
 
 use crate::{
     editor::Editor,
-    game::{Game, create_target_projection},
+    game::{Game, Scaling, create_target_projection, draw_letterbox},
 };
 
 mod editor;
 mod game;
 mod grid;
+mod layers;
 mod materials;
 mod player;
 mod room;
+mod snapshot;
 mod sprite;
 mod world;
 mod camera;
@@ -38,17 +41,104 @@ mod camera;
 extern crate nalgebra_glm as glm;
 
 pub static mut WINDOW_SIZE: (u32, u32) = (0, 0);
+// Scaling the projections below were computed with
+pub static mut WINDOW_SCALING: Scaling = Scaling::Integer;
 
 pub static mut GAME_TO_SCREEN_PROJECTION: glm::Mat4 = IDENTITY;
 pub static mut SCREEN_TO_GAME_PROJECTION: glm::Mat4 = IDENTITY;
 
+// Bump when Game or Editor change in a way their size alone doesn't reveal (e.g. reordered fields).
+const STATE_VERSION: u64 = 1;
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_abi() -> GameAbi {
+    GameAbi {
+        abi_version: GAME_ABI_VERSION,
+        layout_version: STATE_VERSION << 32
+            | (std::mem::size_of::<Game>() + std::mem::size_of::<Editor>()) as u64,
+    }
+}
+
+/**
+ * Invoked by the host right after this dll is loaded.
+ * If the memory is already initialized it was laid out by a compatible dll and is used as is.
+ */
+#[unsafe(no_mangle)]
+pub extern "C" fn on_load(game_memory: &mut GameMemory) {
+    if game_memory.initialized {
+        game_memory.snapshot.clear();
+    }
+}
+
+/**
+ * Invoked by the host right before this dll is unloaded.
+ * When `discard_state` is set the next dll can't read our memory layout: serialize what
+ * we want to keep and run destructors now, while the code that knows the layout is still loaded.
+ */
+#[unsafe(no_mangle)]
+pub extern "C" fn on_unload(game_memory: &mut GameMemory, discard_state: bool) {
+    save_state(game_memory);
+    game_memory.game_target = None;
+
+    if discard_state && game_memory.initialized {
+        unsafe {
+            let game_ptr = game_memory.storage as *mut Game;
+            std::ptr::drop_in_place(game_ptr.add(1) as *mut Editor);
+            std::ptr::drop_in_place(game_ptr);
+        }
+        game_memory.initialized = false;
+    }
+}
+
+/**
+ * Serializes the current state into GameMemory::snapshot (see snapshot.rs).
+ * The host uses it to rewind the game to the start of an input recording.
+ */
+#[unsafe(no_mangle)]
+pub extern "C" fn save_state(game_memory: &mut GameMemory) {
+    game_memory.snapshot.clear();
+    if !game_memory.initialized {
+        return;
+    }
+    let game = unsafe { &*(game_memory.storage as *const Game) };
+    let editor = unsafe { &*((game_memory.storage as *const Game).add(1) as *const Editor) };
+    snapshot::write(&mut game_memory.snapshot, game, editor);
+}
+
+/**
+ * Overwrites the current state with GameMemory::snapshot.
+ * If the game isn't initialized yet, the snapshot is applied once it is.
+ */
+#[unsafe(no_mangle)]
+pub extern "C" fn load_state(game_memory: &mut GameMemory) {
+    if !game_memory.initialized {
+        return;
+    }
+    let game = unsafe { &mut *(game_memory.storage as *mut Game) };
+    let editor = unsafe { &mut *((game_memory.storage as *mut Game).add(1) as *mut Editor) };
+    snapshot::read(&game_memory.snapshot, game, editor);
+    game_memory.snapshot.clear();
+}
+
+/**
+ * Invoked by the host after a reload that changed the state layout (GameAbi::layout_version).
+ * Returns false if the snapshot can't be read, in which case the game starts from scratch.
+ */
+#[unsafe(no_mangle)]
+pub extern "C" fn migrate(game_memory: &mut GameMemory, _from_layout_version: u64) -> bool {
+    snapshot::is_compatible(&game_memory.snapshot)
+}
+
+/**
+ * Advances the game by one fixed timestep. Input is handled here, and so is the GUI:
+ * windows are built during the update and drawn by the following renders.
+ */
 #[unsafe(no_mangle)]
 pub extern "C" fn update_game(
     game_memory: &mut GameMemory,
-    batch: &mut Batch,
-    screen_target: &mut RenderTarget,
     keyboard: &Keyboard,
     mouse: &Mouse,
+    time: &Time,
     device: &Device,
 ) {
     if !game_memory.initialized {
@@ -65,7 +155,13 @@ pub extern "C" fn update_game(
 
             // TODO: Do not write editor in Release
             let editor_ptr = game_ptr.add(1) as *mut Editor;
-            editor_ptr.write(Editor::default());
+            editor_ptr.write(Editor::new(device.clone()));
+
+            // Coming back from a reload that changed the state layout
+            if !game_memory.snapshot.is_empty() {
+                snapshot::read(&game_memory.snapshot, &mut *game_ptr, &mut *editor_ptr);
+                game_memory.snapshot.clear();
+            }
 
             // TODO: clear memory on game exit (no destructors are being invoked here!)
             // SDL primitives are allocated outside this storage. Consider sdl3_sys SetMemoryFunctions()
@@ -84,14 +180,8 @@ pub extern "C" fn update_game(
 
         KEYBOARD = keyboard as *const Keyboard;
         MOUSE = mouse as *const Mouse;
+        TIME = time as *const Time;
         GUI = &mut game.gui as *mut Gui;
-
-        if WINDOW_SIZE.0 != screen_target.width || WINDOW_SIZE.1 != screen_target.height {
-            GAME_TO_SCREEN_PROJECTION = create_target_projection(&game.game_target, screen_target);
-            SCREEN_TO_GAME_PROJECTION = GAME_TO_SCREEN_PROJECTION.try_inverse().unwrap();
-            WINDOW_SIZE.0 = screen_target.width;
-            WINDOW_SIZE.1 = screen_target.height;
-        }
     }
 
     if Keyboard::pressed(common::Keycode::Escape) {
@@ -104,33 +194,102 @@ pub extern "C" fn update_game(
     }
 
     Gui::update(); // Detect input before anything else
-    batch.clear();
 
     if editor.is_showing {
         editor.update(&mut game.world, &game.tile_atlas);
-        editor.render(batch, &game.world, &game.tile_atlas);
     } else {
         game.update();
-        game.render(batch);
-
-        let draw_count = batch.get_batch_count();
 
         let window = Gui::window("Offscreen targets");
         window.set_direction(common::ui::utils::Direction::Vertical);
         window.add_widget(Widget::Texture(game.game_target.color()));
         // window.add_widget(Widget::Texture(game.editor.editor_target.color()));
-        window.add_widget(Widget::Text(format!("Draw calls: {}", draw_count)));
-        batch.clear();
+        let stats = &game.batch_stats;
+        window.add_widget(Widget::Text(format!(
+            "Draw calls: {} ({} flushes)",
+            stats.draws, stats.flushes
+        )));
+        window.add_widget(Widget::Text(format!(
+            "Vertices: {}/{} ({:.0}% of budget)",
+            stats.peak_vertices,
+            stats.vertex_capacity,
+            stats.budget_used() * 100f32
+        )));
+        window.add_widget(Widget::Text(format!(
+            "Time: {:.2}s, dropped frames: {}",
+            Time::total(),
+            Time::dropped_frames()
+        )));
 
         if Keyboard::pressed(common::Keycode::E)
             || window.add_widget(Widget::Button("Edit Room", [20, 132, 23, 255]))
         {
             editor.is_showing = true;
         }
+    }
+}
+
+/**
+ * Draws the latest update, blended with the one before it by Time::alpha.
+ * Must not change the simulation: it may run several times in a row, or not at all.
+ * It only writes what it draws (render targets, GameMemory::game_target), the projections
+ * cached for the current window size, and Game::batch_stats for the next update to show.
+ */
+#[unsafe(no_mangle)]
+pub extern "C" fn render_game(
+    game_memory: &mut GameMemory,
+    batch: &mut Batch,
+    screen_target: &RenderTarget,
+    time: &Time,
+) {
+    // Input and GUI globals are bound by update_game, nothing to draw until it ran once.
+    if !game_memory.initialized || unsafe { MOUSE.is_null() } {
+        return;
+    }
+
+    let game: &mut Game = unsafe { &mut *(game_memory.storage as *mut Game) };
+    let editor: &Editor = unsafe { &*((game_memory.storage as *mut Game).add(1) as *const Editor) };
+
+    unsafe {
+        TIME = time as *const Time;
+
+        // A minimized window is 0x0 and has no inverse projection, keep the previous ones
+        let visible = screen_target.width > 0 && screen_target.height > 0;
+        if visible
+            && (WINDOW_SIZE.0 != screen_target.width
+                || WINDOW_SIZE.1 != screen_target.height
+                || WINDOW_SCALING != game.scaling)
+        {
+            GAME_TO_SCREEN_PROJECTION =
+                create_target_projection(&game.game_target, screen_target, game.scaling);
+            // Mouse positions are mapped back into the game target with the inverse
+            SCREEN_TO_GAME_PROJECTION = GAME_TO_SCREEN_PROJECTION.try_inverse().unwrap();
+            WINDOW_SIZE.0 = screen_target.width;
+            WINDOW_SIZE.1 = screen_target.height;
+            WINDOW_SCALING = game.scaling;
+        }
+    }
+
+    batch.clear();
+
+    if editor.is_showing {
+        editor.render(batch, &game.world, &game.tile_atlas);
+    } else {
+        game.render(batch);
+        batch.clear();
+        let output = game.post_process.apply(batch, &game.game_target);
+        game_memory.game_target = Some(output.clone());
 
         batch.push_matrix(unsafe { GAME_TO_SCREEN_PROJECTION });
-        batch.texture(game.game_target.color(), &VEC_2_ZERO);
+        batch.texture(output, &VEC_2_ZERO);
         batch.pop_matrix();
+        draw_letterbox(
+            batch,
+            &game.game_target,
+            screen_target,
+            &unsafe { GAME_TO_SCREEN_PROJECTION },
+            game.letterbox_color,
+        );
     }
 
     // Draw to screen window
@@ -147,4 +306,6 @@ pub extern "C" fn update_game(
         batch.draw_into(&screen_target); // Flush the batch into the screen
         batch.clear();
     }
+
+    game.batch_stats = batch.take_stats();
 }
@@ -1,27 +1,79 @@
 use common::{
     TextureFormat,
-    graphics::material::{MaterialSpecification, ShaderSpecification},
+    graphics::{
+        material::{MaterialSpecification, PipelineSpecification, ShaderSpecification},
+        shader_reload::{ShaderCompiler, ShaderHotReload},
+    },
 };
 
 // TODO: find a way to store Shaders in common/ and game/ and re compile them easily.
-static RED_SHADER_FRAGMENT_SRC: &str = include_str!(
-    "/Users/feresr/Workspace/learn_sdl3_gpu/common/src/shaders/compiled/red.fragment.msl"
-);
-static RED_SHADER_VERTEX_SRC: &str = include_str!(
-    "/Users/feresr/Workspace/learn_sdl3_gpu/common/src/shaders/compiled/red.vertex.msl"
-);
+static RED_SHADER_FRAGMENT_MSL: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../common/src/shaders/compiled/red.fragment.msl"
+));
+static RED_SHADER_FRAGMENT_SPIRV: &[u8] = include_bytes!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../common/src/shaders/compiled/red.fragment.spv"
+));
+static RED_SHADER_VERTEX_MSL: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../common/src/shaders/compiled/red.vertex.msl"
+));
+static RED_SHADER_VERTEX_SPIRV: &[u8] = include_bytes!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../common/src/shaders/compiled/red.vertex.spv"
+));
+static RED_SHADER_SOURCE: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../common/src/shaders/red.hlsl"
+));
 
 pub const RED_MATERIAL: MaterialSpecification = MaterialSpecification {
     name: "RED MATERIAL",
     fragment: ShaderSpecification {
-        src: RED_SHADER_FRAGMENT_SRC,
-        sampler_count: 1,
+        msl: Some(RED_SHADER_FRAGMENT_MSL),
+        spirv: Some(RED_SHADER_FRAGMENT_SPIRV),
+        dxil: None,
+        // Reflected from source
         uniform_buffer_count: 0,
+        sampler_count: 0,
     },
     vertex: ShaderSpecification {
-        src: RED_SHADER_VERTEX_SRC,
-        uniform_buffer_count: 1,
+        msl: Some(RED_SHADER_VERTEX_MSL),
+        spirv: Some(RED_SHADER_VERTEX_SPIRV),
+        dxil: None,
+        // Reflected from source
+        uniform_buffer_count: 0,
         sampler_count: 0,
     },
     texture_format: TextureFormat::R8g8b8a8Unorm,
+    pipeline: PipelineSpecification::DEFAULT,
+    texture_slots: &[],
+    source: Some(RED_SHADER_SOURCE),
+    hot_reload: if cfg!(debug_assertions) {
+        Some(ShaderHotReload {
+            path: concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../common/src/shaders/red.hlsl"
+            ),
+            compiler: ShaderCompiler::SHADERCROSS,
+        })
+    } else {
+        None
+    },
 };
+
+/**
+ * RED_MATERIAL's fragment cbuffer (red.hlsl).
+ */
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RedUniforms {
+    // Pulses towards tint, alpha is the strength
+    pub tint: [f32; 4],
+    pub time: f32,
+    pub _padding: [f32; 3],
+}
+
+// No padding bytes, see UniformBlock::new
+const _: () = assert!(size_of::<RedUniforms>() == 8 * size_of::<f32>());
@@ -1,11 +1,17 @@
 use common::{
-    Device, Point, Rect,
-    graphics::{batch::Batch, texture::Texture},
+    Point, Rect,
+    graphics::{batch::Batch, subtexture::Subtexture},
     input::keyboard::Keyboard,
+    time::Time,
     utils::approach,
 };
 
-use crate::{room::Room, sprite::Sprite};
+use crate::{
+    layers,
+    room::Room,
+    snapshot::{Reader, Writer},
+    sprite::Sprite,
+};
 
 struct Mover {
     speed: glm::Vec2,
@@ -33,7 +39,7 @@ impl Mover {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 enum State {
     ATTACK,
     IDLE,
@@ -43,6 +49,8 @@ enum State {
 
 pub struct Player {
     position: Point,
+    // Position before the last update, rendering blends the two (see Time::alpha)
+    previous_position: Point,
     mover: Mover,
     collider: Rect,
     pivot: Point,
@@ -52,17 +60,19 @@ pub struct Player {
 }
 
 // TODO: fix filepath (use relative)
-static PLAYER_PNG: &[u8] =
+pub(crate) static PLAYER_PNG: &[u8] =
     include_bytes!("/Users/feresr/Workspace/learn_sdl3_gpu/game/assets/player.png");
 static PLAYER_ATLAS: &str =
     include_str!("/Users/feresr/Workspace/learn_sdl3_gpu/game/assets/player.atlas");
 
 impl Player {
-    pub fn new(device: Device) -> Player {
-        let texture = Texture::from_bytes(device, PLAYER_PNG);
-
+    /**
+     * `texture` is PLAYER_PNG, or its region in a packed atlas.
+     */
+    pub fn new(texture: Subtexture) -> Player {
         Self {
             position: Point::new(32, 32),
+            previous_position: Point::new(32, 32),
             mover: Default::default(),
             collider: Rect::new(0, 0, 8, 8),
             pivot: Point::new(-4, -8),
@@ -76,8 +86,41 @@ impl Player {
         self.position
     }
 
+    pub(crate) fn write_snapshot(&self, writer: &mut Writer) {
+        writer.i32(self.position.x);
+        writer.i32(self.position.y);
+        writer.f32(self.mover.speed.x);
+        writer.f32(self.mover.speed.y);
+        writer.f32(self.mover.reminder.x);
+        writer.f32(self.mover.reminder.y);
+        writer.i32(self.collider.x);
+        writer.i32(self.collider.y);
+        writer.bool(self.grounded);
+        writer.u8(self.state.clone() as u8);
+        self.sprite.write_snapshot(writer);
+    }
+
+    pub(crate) fn read_snapshot(&mut self, reader: &mut Reader) -> Option<()> {
+        self.position = Point::new(reader.i32()?, reader.i32()?);
+        self.previous_position = self.position;
+        self.mover.speed = glm::vec2(reader.f32()?, reader.f32()?);
+        self.mover.reminder = glm::vec2(reader.f32()?, reader.f32()?);
+        self.collider.reposition(Point::new(reader.i32()?, reader.i32()?));
+        self.grounded = reader.bool()?;
+        self.state = match reader.u8()? {
+            0 => State::ATTACK,
+            1 => State::IDLE,
+            2 => State::RUN,
+            3 => State::JUMP,
+            _ => return None,
+        };
+        self.sprite.read_snapshot(reader)
+    }
+
     pub fn update(&mut self, room: &Room) {
+        self.previous_position = self.position;
         self.sprite.update();
+        self.sprite.debug_window();
 
         if self.sprite.looping {
             self.state = State::IDLE;
@@ -188,7 +231,14 @@ impl Player {
     }
 
     pub fn render(&self, batch: &mut Batch) {
-        self.sprite.render(&self.position, batch);
+        let alpha = Time::alpha();
+        let position = Point::new(
+            lerp(self.previous_position.x, self.position.x, alpha),
+            lerp(self.previous_position.y, self.position.y, alpha),
+        );
+        batch.push_layer(layers::PLAYER);
+        self.sprite.render(&position, batch);
+        batch.pop_layer();
 
         // DEBUG collider
         // batch.rect(
@@ -207,3 +257,7 @@ impl Player {
         // );
     }
 }
+
+fn lerp(from: i32, to: i32, alpha: f32) -> i32 {
+    (from as f32 + (to - from) as f32 * alpha).round() as i32
+}
@@ -1,6 +1,10 @@
 use common::{Point, Rect, utils::tile_atlas::TileAtlas};
 
-use crate::grid::Grid;
+use crate::{
+    grid::Grid,
+    layers,
+    snapshot::{Reader, Writer},
+};
 
 pub const TILE_SIZE: usize = 8;
 pub const COLUMNS: usize = 40;
@@ -44,6 +48,7 @@ impl Room {
 
     pub(crate) fn render(&self, batch: &mut common::graphics::batch::Batch, atlas: &TileAtlas) {
         let mut tile_position = glm::vec2(0f32, 0f32);
+        batch.push_layer(layers::BACKGROUND);
         for (x, y, tile) in &self.background_tiles {
             if !tile.visible {
                 continue;
@@ -55,7 +60,9 @@ impl Room {
             tile_position.y += y as f32 * TILE_SIZE as f32;
             batch.subtexture_color(sprite, tile_position, [110, 110, 110, 255]);
         }
+        batch.pop_layer();
 
+        batch.push_layer(layers::FOREGROUND);
         for (x, y, tile) in &self.foreground_tiles {
             if !tile.visible {
                 continue;
@@ -67,6 +74,40 @@ impl Room {
             tile_position.y += y as f32 * TILE_SIZE as f32;
             batch.subtexture(sprite, tile_position);
         }
+        batch.pop_layer();
+    }
+
+    // Bytes write_snapshot produces for every room
+    pub const SNAPSHOT_SIZE: usize = 2 * 4 + 2 * TILE_COUNT * 2;
+
+    pub(crate) fn write_snapshot(&self, writer: &mut Writer) {
+        writer.i32(self.position_in_world.x);
+        writer.i32(self.position_in_world.y);
+        for tile in self
+            .foreground_tiles
+            .inner
+            .iter()
+            .chain(&self.background_tiles.inner)
+        {
+            writer.u8(tile.id);
+            writer.bool(tile.visible);
+        }
+    }
+
+    pub(crate) fn read_snapshot(&mut self, reader: &mut Reader) -> Option<()> {
+        self.position_in_world = Point::new(reader.i32()?, reader.i32()?);
+        for tile in self
+            .foreground_tiles
+            .inner
+            .iter_mut()
+            .chain(self.background_tiles.inner.iter_mut())
+        {
+            *tile = Tile {
+                id: reader.u8()?,
+                visible: reader.bool()?,
+            };
+        }
+        Some(())
     }
 
     pub fn collides(&self, rect: &Rect) -> bool {
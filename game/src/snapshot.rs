@@ -0,0 +1,262 @@
+use crate::{editor::Editor, game::Game, world::World};
+
+/**
+ * Layout-independent copy of the game state. Used to carry the state across hot reloads
+ * that change how Game / Editor are laid out in memory, and by the host to rewind the
+ * game to the start of an input recording.
+ *
+ * [version: u32][player..][editor..][world len: u32][rooms..]
+ * room: [x: i32][y: i32][foreground tiles..][background tiles..], tile: [id: u8][visible: bool]
+ *
+ * Every field is decoded on its own, corrupt values (e.g. a bool that isn't 0 or 1) fail the read.
+ *
+ * Everything that influences the simulation must be in here, otherwise replays diverge.
+ * The camera is left out on purpose: it re-targets the player's room on the next update.
+ *
+ * Only bump SNAPSHOT_VERSION when this format changes, not when Game does.
+ */
+const SNAPSHOT_VERSION: u32 = 3;
+
+pub fn write(out: &mut Vec<u8>, game: &Game, editor: &Editor) {
+    let mut writer = Writer { bytes: out };
+    writer.u32(SNAPSHOT_VERSION);
+    game.player.write_snapshot(&mut writer);
+    editor.write_snapshot(&mut writer);
+
+    writer.u32(World::SNAPSHOT_SIZE as u32);
+    game.world.write_snapshot(&mut writer);
+}
+
+pub fn is_compatible(snapshot: &[u8]) -> bool {
+    snapshot.len() >= 4 && snapshot[..4] == SNAPSHOT_VERSION.to_le_bytes()
+}
+
+/**
+ * Restores whatever the snapshot holds on top of an existing Game / Editor.
+ * Sections that no longer fit (e.g. the world after Room changed size) are skipped.
+ * Reading stops at the first truncated or corrupt value, leaving the rest as it was.
+ */
+pub fn read(snapshot: &[u8], game: &mut Game, editor: &mut Editor) {
+    if !is_compatible(snapshot) {
+        return;
+    }
+    let mut reader = Reader {
+        bytes: snapshot,
+        offset: 4,
+    };
+
+    if game.player.read_snapshot(&mut reader).is_none()
+        || editor.read_snapshot(&mut reader).is_none()
+    {
+        eprintln!("Truncated or corrupt snapshot, game state partially restored");
+        return;
+    }
+
+    let Some(world_len) = reader.u32() else {
+        return;
+    };
+    if world_len as usize != World::SNAPSHOT_SIZE {
+        eprintln!("World layout changed, keeping the level from disk");
+    } else if game.world.read_snapshot(&mut reader).is_none() {
+        eprintln!("Truncated or corrupt snapshot, world partially restored");
+    }
+}
+
+pub(crate) struct Writer<'a> {
+    bytes: &'a mut Vec<u8>,
+}
+
+impl Writer<'_> {
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.bytes.push(value as u8);
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn f32(&mut self, value: f32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.u16(value.len() as u16);
+        self.bytes(value.as_bytes());
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.offset..self.offset + len)?;
+        self.offset += len;
+        Some(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    pub fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Option<i32> {
+        Some(i32::from_le_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.array()?))
+    }
+
+    pub fn str(&mut self) -> Option<&'a str> {
+        let len = self.u16()? as usize;
+        std::str::from_utf8(self.take(len)?).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::Point;
+
+    use super::*;
+    use crate::room::{Room, TILE_COUNT, Tile};
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let mut bytes = Vec::new();
+        let mut writer = Writer { bytes: &mut bytes };
+        writer.bool(true);
+        writer.u8(7);
+        writer.u16(0xbeef);
+        writer.u32(SNAPSHOT_VERSION);
+        writer.i32(-42);
+        writer.f32(1.5);
+        writer.str("room");
+        writer.bytes(&[1, 2, 3]);
+
+        let mut reader = Reader {
+            bytes: &bytes,
+            offset: 0,
+        };
+        assert_eq!(reader.bool(), Some(true));
+        assert_eq!(reader.u8(), Some(7));
+        assert_eq!(reader.u16(), Some(0xbeef));
+        assert_eq!(reader.u32(), Some(SNAPSHOT_VERSION));
+        assert_eq!(reader.i32(), Some(-42));
+        assert_eq!(reader.f32(), Some(1.5));
+        assert_eq!(reader.str(), Some("room"));
+        assert_eq!(reader.take(3), Some([1u8, 2, 3].as_slice()));
+        assert_eq!(reader.u8(), None);
+    }
+
+    #[test]
+    fn truncated_values_read_as_none() {
+        let mut reader = Reader {
+            bytes: &[1, 2, 3],
+            offset: 0,
+        };
+        assert_eq!(reader.u32(), None);
+        // A failed read consumes nothing
+        assert_eq!(reader.u16(), Some(0x0201));
+
+        let mut bytes = Vec::new();
+        Writer { bytes: &mut bytes }.str("truncated");
+        bytes.pop();
+        let mut reader = Reader {
+            bytes: &bytes,
+            offset: 0,
+        };
+        assert_eq!(reader.str(), None);
+    }
+
+    #[test]
+    fn corrupt_bools_read_as_none() {
+        let mut reader = Reader {
+            bytes: &[0, 1, 2],
+            offset: 0,
+        };
+        assert_eq!(reader.bool(), Some(false));
+        assert_eq!(reader.bool(), Some(true));
+        assert_eq!(reader.bool(), None);
+    }
+
+    #[test]
+    fn rooms_read_back_field_by_field() {
+        let mut room = Room::empty(Point::new(320, 176));
+        room.foreground_tiles.inner[3] = Tile {
+            id: 42,
+            visible: true,
+        };
+        room.background_tiles.inner[TILE_COUNT - 1] = Tile {
+            id: 7,
+            visible: true,
+        };
+        let mut bytes = Vec::new();
+        room.write_snapshot(&mut Writer { bytes: &mut bytes });
+        assert_eq!(bytes.len(), Room::SNAPSHOT_SIZE);
+
+        let mut read = Room::empty(Point::new(0, 0));
+        let mut reader = Reader {
+            bytes: &bytes,
+            offset: 0,
+        };
+        assert_eq!(read.read_snapshot(&mut reader), Some(()));
+        assert_eq!(read.position_in_world, Point::new(320, 176));
+        let tile = read.foreground_tiles.inner[3];
+        assert_eq!((tile.id, tile.visible), (42, true));
+        let tile = read.background_tiles.inner[TILE_COUNT - 1];
+        assert_eq!((tile.id, tile.visible), (7, true));
+
+        // The `visible` of the first foreground tile
+        bytes[9] = 2;
+        let mut reader = Reader {
+            bytes: &bytes,
+            offset: 0,
+        };
+        assert_eq!(read.read_snapshot(&mut reader), None);
+    }
+
+    #[test]
+    fn only_the_current_version_is_compatible() {
+        assert!(is_compatible(&SNAPSHOT_VERSION.to_le_bytes()));
+        assert!(!is_compatible(&(SNAPSHOT_VERSION + 1).to_le_bytes()));
+        assert!(!is_compatible(&[]));
+    }
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use common::{
     Point, Rect,
-    graphics::{VEC_2_ZERO, subtexture::Subtexture, texture::Texture},
+    graphics::{VEC_2_ZERO, subtexture::Subtexture},
     ui::{gui::Gui, widget::Widget},
     utils::{
         animation::{Animation, Frame},
@@ -10,6 +10,8 @@ use common::{
     },
 };
 
+use crate::snapshot::{Reader, Writer};
+
 /**
 Sprite
  ├─ Animation (e.g., "idle")
@@ -39,7 +41,7 @@ pub struct Sprite {
 
 impl Sprite {
     pub(crate) fn from_atlas(
-        texture: Texture, // TODO bake texture name into source (atlas)? To avoid passing two textures here
+        texture: Subtexture, // TODO bake texture name into source (atlas)? To avoid passing two textures here
         source: &str,
     ) -> Self {
         let parser = AtlasParser::texture_and_source(texture, source);
@@ -92,7 +94,33 @@ impl Sprite {
         self.timer = self.frames[self.frame_index as usize].duration;
     }
 
-    pub(crate) fn render(&self, position: &Point, batch: &mut common::graphics::batch::Batch) {
+    pub(crate) fn write_snapshot(&self, writer: &mut Writer) {
+        writer.u8(self.frame_index);
+        writer.bool(self.looping);
+        writer.u32(self.timer);
+        writer.str(self.playing.as_ref().map_or("", |anim| anim.name.as_str()));
+        writer.f32(self.scale_x);
+        writer.f32(self.scale_y);
+        writer.bool(self.flip_x);
+        writer.bool(self.flip_y);
+    }
+
+    pub(crate) fn read_snapshot(&mut self, reader: &mut Reader) -> Option<()> {
+        self.frame_index = reader.u8()?;
+        self.looping = reader.bool()?;
+        self.timer = reader.u32()?;
+        self.playing = self.animations.get(reader.str()?).cloned();
+        self.scale_x = reader.f32()?;
+        self.scale_y = reader.f32()?;
+        self.flip_x = reader.bool()?;
+        self.flip_y = reader.bool()?;
+        Some(())
+    }
+
+    /**
+     * Debug GUI, built during the update (the GUI only takes input there).
+     */
+    pub(crate) fn debug_window(&self) {
         let window = Gui::window("SPRITE");
         window.set_direction(common::ui::utils::Direction::Vertical);
 
@@ -110,7 +138,9 @@ impl Sprite {
                 name, anim.from, anim.to
             )));
         }
+    }
 
+    pub(crate) fn render(&self, position: &Point, batch: &mut common::graphics::batch::Batch) {
         let frame = &self.frames[self.frame_index as usize];
         let mut subtexture = frame.subtexture.clone();
         subtexture.flip(self.flip_x, self.flip_y);
@@ -132,7 +162,7 @@ struct AtlasParser {
 }
 
 impl AtlasParser {
-    pub fn texture_and_source(texture: Texture, source: &str) -> Self {
+    pub fn texture_and_source(texture: Subtexture, source: &str) -> Self {
         let mut frames: Vec<Frame> = Default::default();
         let mut animations: HashMap<String, Animation> = Default::default();
         for line in source.lines() {
@@ -178,7 +208,7 @@ impl AtlasParser {
 
                 let rect = Rect::new(x, y, w, h);
                 let frame = Frame {
-                    subtexture: Subtexture::new(texture.clone(), rect),
+                    subtexture: texture.region(rect),
                     duration,
                     pivot: Point::new(pivot_x, pivot_y),
                 };
@@ -5,6 +5,7 @@ use common::{IOStream, Point, utils::tile_atlas::TileAtlas};
 use crate::{
     grid::Grid,
     room::{ROOM_HEIGHT, ROOM_WIDTH, Room},
+    snapshot::{Reader, Writer},
 };
 
 const LEVEL_PATH: &str = "/Users/feresr/Workspace/learn_sdl3_gpu/game/assets/level";
@@ -61,15 +62,37 @@ impl World {
         }
     }
 
-    pub fn save(&self) {
-        let mut io = IOStream::from_file(LEVEL_PATH, "wb").unwrap();
-        let bytes: &[u8] = unsafe {
+    /**
+     * Raw bytes of all rooms, in the same format as the level file.
+     */
+    pub fn bytes(&self) -> &[u8] {
+        unsafe {
             std::slice::from_raw_parts(
                 self.rooms.inner.as_ptr() as *const u8,
                 std::mem::size_of_val(&self.rooms.inner),
             )
-        };
-        io.write(bytes).unwrap();
+        }
+    }
+
+    // Bytes write_snapshot produces
+    pub const SNAPSHOT_SIZE: usize = ROOMS_IN_WORLD * Room::SNAPSHOT_SIZE;
+
+    pub(crate) fn write_snapshot(&self, writer: &mut Writer) {
+        for room in &self.rooms.inner {
+            room.write_snapshot(writer);
+        }
+    }
+
+    pub(crate) fn read_snapshot(&mut self, reader: &mut Reader) -> Option<()> {
+        for room in self.rooms.inner.iter_mut() {
+            room.read_snapshot(reader)?;
+        }
+        Some(())
+    }
+
+    pub fn save(&self) {
+        let mut io = IOStream::from_file(LEVEL_PATH, "wb").unwrap();
+        io.write_all(self.bytes()).unwrap();
         dbg!("Room saved!");
     }
 }
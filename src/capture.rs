@@ -0,0 +1,180 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use common::{
+    game_memory::GameMemory,
+    graphics::{render_target::RenderTarget, texture::Download},
+    utils::png,
+};
+use sdl3::gpu::CommandBuffer;
+
+pub const CAPTURE_DIRECTORY: &str = "captures";
+
+#[derive(Clone, Copy, Debug)]
+pub enum CaptureSource {
+    // The window, GUI included
+    Screen,
+    // GameMemory::game_target: the game at its own resolution, post-processed
+    GameTarget,
+}
+
+/**
+ * Saves PNGs of the rendered frames into CAPTURE_DIRECTORY:
+ *
+ *   F12         screenshot of the screen
+ *   Shift+F12   screenshot of the game target
+ *   F10         start / stop a sequence: the game target of every render, one numbered PNG each
+ *
+ * Sequences are meant to be turned into GIFs afterwards, e.g.
+ *
+ *   ffmpeg -framerate 60 -i captures/sequence-<time>/%05d.png capture.gif
+ *
+ * Every capture reads the frame back from the GPU and waits for it: the game slows down
+ * while a sequence is being captured. Every frame:
+ *
+ *   capture.before_submit(&device, &screen_target, &cmd); // While the swapchain texture is set
+ *   // Submit cmd, and wait for it if capture.waits_for_frame()
+ *   capture.after_submit(&device, &game_memory);
+ */
+pub struct Capture {
+    screenshot: Option<CaptureSource>,
+    // Screenshot of the screen recorded by before_submit, written by after_submit
+    screen: Option<(PathBuf, Download)>,
+    // Directory and next frame number
+    sequence: Option<(PathBuf, u32)>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Capture {
+            screenshot: None,
+            screen: None,
+            sequence: None,
+        }
+    }
+
+    /**
+     * Taken after the next render.
+     */
+    pub fn request_screenshot(&mut self, source: CaptureSource) {
+        self.screenshot = Some(source);
+    }
+
+    pub fn toggle_sequence(&mut self) {
+        if let Some((directory, frames)) = self.sequence.take() {
+            println!("Captured {} frames into {}", frames, directory.display());
+            return;
+        }
+        let directory = Path::new(CAPTURE_DIRECTORY).join(format!("sequence-{}", timestamp()));
+        match std::fs::create_dir_all(&directory) {
+            Ok(()) => {
+                println!(
+                    "Capturing frames into {} (F10 to stop)",
+                    directory.display()
+                );
+                self.sequence = Some((directory, 0));
+            }
+            Err(e) => eprintln!("Could not create {}: {}", directory.display(), e),
+        }
+    }
+
+    /**
+     * Runs after every render, while the screen target still has the frame's swapchain texture:
+     * screenshots of the screen are copied by `command_buffer`, the frame's own.
+     */
+    pub fn before_submit(
+        &mut self,
+        device: &sdl3::gpu::Device,
+        screen_target: &RenderTarget,
+        command_buffer: &CommandBuffer,
+    ) {
+        if let Some(CaptureSource::Screen) = self.screenshot {
+            self.screenshot = None;
+            let download = screen_target.record_download(device, command_buffer);
+            self.screen = Some((screenshot_path(), download));
+        }
+    }
+
+    /**
+     * The frame's command buffer has to be finished before after_submit reads the screen.
+     */
+    pub fn waits_for_frame(&self) -> bool {
+        self.screen.is_some()
+    }
+
+    /**
+     * Runs after the frame's command buffer was submitted (and waited for, see waits_for_frame).
+     */
+    pub fn after_submit(&mut self, device: &sdl3::gpu::Device, game_memory: &GameMemory) {
+        if let Some((path, download)) = self.screen.take() {
+            let (width, height) = (download.width(), download.height());
+            let pixels = download.pixels(device);
+            if create_capture_directory() && write_png(&path, width, height, &pixels) {
+                println!("Screenshot written to {}", path.display());
+            }
+        }
+
+        if let Some(CaptureSource::GameTarget) = self.screenshot.take() {
+            let path = screenshot_path();
+            if create_capture_directory() && write_game_target(game_memory, &path) {
+                println!("Screenshot written to {}", path.display());
+            }
+        }
+
+        if let Some((directory, frame)) = self.sequence.as_mut() {
+            let path = directory.join(format!("{:05}.png", frame));
+            if write_game_target(game_memory, &path) {
+                *frame += 1;
+            } else {
+                // Don't retry every frame
+                self.toggle_sequence();
+            }
+        }
+    }
+}
+
+fn screenshot_path() -> PathBuf {
+    return Path::new(CAPTURE_DIRECTORY).join(format!("screenshot-{}.png", timestamp()));
+}
+
+fn create_capture_directory() -> bool {
+    match std::fs::create_dir_all(CAPTURE_DIRECTORY) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Could not create {}: {}", CAPTURE_DIRECTORY, e);
+            false
+        }
+    }
+}
+
+fn write_game_target(game_memory: &GameMemory, path: &Path) -> bool {
+    let Some(texture) = game_memory.game_target.as_ref() else {
+        eprintln!("Nothing to capture: the game did not render its game target");
+        return false;
+    };
+    return write_png(
+        path,
+        texture.width(),
+        texture.height(),
+        &texture.read_pixels(),
+    );
+}
+
+fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> bool {
+    match png::write_rgba(path, width, height, pixels) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Could not write {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+// Milliseconds since the epoch, keeps capture names unique and sorted
+fn timestamp() -> u128 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis());
+}
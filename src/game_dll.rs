@@ -1,86 +1,268 @@
 use common::{
     Device,
-    game_memory::GameMemory,
+    game_memory::{GAME_ABI_VERSION, GameAbi, GameMemory},
     graphics::{batch::Batch, render_target::RenderTarget},
-    input::{
-        keyboard::Keyboard,
-        mouse::Mouse,
-    },
+    input::{keyboard::Keyboard, mouse::Mouse},
+    time::Time,
 };
 use sdl3::sys::{
     loadso::{SDL_LoadFunction, SDL_LoadObject, SDL_SharedObject, SDL_UnloadObject},
     stdinc::SDL_FunctionPointer,
 };
 
-use std::ffi::CStr;
+use std::{
+    error::Error,
+    ffi::{CStr, CString},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
 
-type UpdateFn =
-    extern "C" fn(&mut GameMemory, &mut Batch, &RenderTarget, &Keyboard, &Mouse, &Device);
+type UpdateFn = extern "C" fn(&mut GameMemory, &Keyboard, &Mouse, &Time, &Device);
+type RenderFn = extern "C" fn(&mut GameMemory, &mut Batch, &RenderTarget, &Time);
+type GameAbiFn = extern "C" fn() -> GameAbi;
+type OnLoadFn = extern "C" fn(&mut GameMemory);
+type OnUnloadFn = extern "C" fn(&mut GameMemory, bool);
+type MigrateFn = extern "C" fn(&mut GameMemory, u64) -> bool;
+type StateFn = extern "C" fn(&mut GameMemory);
 
-/* Loads the game dynamically, finds and exposes a reference to its update function */
+/* Loads the game dynamically, finds and exposes a reference to its update and render functions */
 pub struct GameDll {
-    handle: *mut SDL_SharedObject,
+    abi: GameAbi,
     update: UpdateFn,
+    render: RenderFn,
+    on_load: OnLoadFn,
+    on_unload: OnUnloadFn,
+    migrate: MigrateFn,
+    save_state: StateFn,
+    load_state: StateFn,
+    #[allow(dead_code)] // Only held so the code behind the fn pointers above stays mapped
+    library: Library,
+    // Libraries replaced without rebuilding the state: it may still point into them (string
+    // literals like Material::name, vtables, drop glue). Unloaded once the state is rebuilt,
+    // at most MAX_RETIRED_LIBRARIES (see replace)
+    #[allow(dead_code)]
+    retired: Vec<Library>,
+}
+
+/* A loaded private copy of the game library, unloaded and deleted on drop */
+struct Library {
+    handle: *mut SDL_SharedObject,
+    path: PathBuf,
 }
 
 #[cfg(all(debug_assertions, target_os = "macos"))]
-const LIB_PATH: &CStr = c"target/debug/libgame.dylib";
+const LIB_PATH: &str = "target/debug/libgame.dylib";
 
 #[cfg(all(not(debug_assertions), target_os = "macos"))]
-const LIB_PATH: &CStr = c"target/release/libgame.dylib";
+const LIB_PATH: &str = "target/release/libgame.dylib";
 
 #[cfg(all(debug_assertions, target_os = "windows"))]
-const LIB_PATH: &CStr = c"target/debug/game.dll";
+const LIB_PATH: &str = "target/debug/game.dll";
 
 #[cfg(all(not(debug_assertions), target_os = "windows"))]
-const LIB_PATH: &CStr = c"target/release/game.dll";
+const LIB_PATH: &str = "target/release/game.dll";
 
 #[cfg(all(debug_assertions, target_os = "linux"))]
-const LIB_PATH: &CStr = c"target/debug/libgame.so";
+const LIB_PATH: &str = "target/debug/libgame.so";
 
 #[cfg(all(not(debug_assertions), target_os = "linux"))]
-const LIB_PATH: &CStr = c"target/release/libgame.so";
+const LIB_PATH: &str = "target/release/libgame.so";
 
 const UPDATE_GAME: &CStr = c"update_game";
+const RENDER_GAME: &CStr = c"render_game";
+const GAME_ABI: &CStr = c"game_abi";
+const ON_LOAD: &CStr = c"on_load";
+const ON_UNLOAD: &CStr = c"on_unload";
+const MIGRATE: &CStr = c"migrate";
+const SAVE_STATE: &CStr = c"save_state";
+const LOAD_STATE: &CStr = c"load_state";
+
+// Reloads that keep the state in place before one rebuilds it, releasing the old libraries
+const MAX_RETIRED_LIBRARIES: usize = 8;
+
+// Every load gets its own copy of the library (see GameDll::load)
+static NEXT_COPY: AtomicU32 = AtomicU32::new(0);
 
 impl GameDll {
-    pub fn load() -> Self {
-        let update: UpdateFn;
-        let handle;
+    /**
+     * The library cargo builds into. This file is never loaded directly: the linker
+     * would overwrite it while it is still mapped on the next build.
+     */
+    pub fn build_path() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(LIB_PATH)
+    }
 
-        unsafe {
-            handle = SDL_LoadObject(LIB_PATH.as_ptr());
-            if handle.is_null() {
-                panic!("Failed to load DLL");
-            }
+    /**
+     * Copies the freshly built library to a uniquely-named temp file and loads that copy.
+     * On failure nothing is left loaded, so the caller can keep running the previous GameDll.
+     */
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let build_path = Self::build_path();
+        let loaded_path = Self::unique_copy_path(&build_path);
+        std::fs::copy(&build_path, &loaded_path)
+            .map_err(|e| format!("Could not copy {}: {}", build_path.display(), e))?;
+
+        let library = Library::load(loaded_path)?;
+        let game_abi: GameAbiFn = library.symbol(GAME_ABI)?;
 
-            let func: SDL_FunctionPointer = SDL_LoadFunction(handle, UPDATE_GAME.as_ptr());
-            if func.is_none() {
-                panic!("Failed to find symbol");
+        // Never call into a dll built against a different interface, it would read garbage.
+        let abi = game_abi();
+        if abi.abi_version != GAME_ABI_VERSION {
+            return Err(format!(
+                "Incompatible game DLL: ABI version {} (host expects {})",
+                abi.abi_version, GAME_ABI_VERSION
+            )
+            .into());
+        }
+
+        return Ok(GameDll {
+            abi,
+            update: library.symbol(UPDATE_GAME)?,
+            render: library.symbol(RENDER_GAME)?,
+            on_load: library.symbol(ON_LOAD)?,
+            on_unload: library.symbol(ON_UNLOAD)?,
+            migrate: library.symbol(MIGRATE)?,
+            save_state: library.symbol(SAVE_STATE)?,
+            load_state: library.symbol(LOAD_STATE)?,
+            library,
+            retired: Vec::new(),
+        });
+    }
+
+    /**
+     * Gives the game a chance to pick up whatever state is already in `game_memory`.
+     * Call once after the very first load, `replace` takes care of it on hot reloads.
+     */
+    pub fn start(&self, game_memory: &mut GameMemory) {
+        (self.on_load)(game_memory);
+    }
+
+    /**
+     * Swaps this dll for `next`, carrying the game state across.
+     *
+     * If both libraries agree on the state layout, memory is reused as is: the previous library
+     * stays mapped, since the state keeps borrowing from it.
+     * Otherwise, or once MAX_RETIRED_LIBRARIES are kept mapped that way, the old dll serializes
+     * and drops its state before being unloaded (along with every library retired before it),
+     * and the new one rebuilds it from GameMemory::snapshot (or starts fresh if it can't).
+     * Libraries are only unloaded after such a rebuild: nothing built by them survives it.
+     */
+    pub fn replace(&mut self, next: GameDll, game_memory: &mut GameMemory) {
+        let previous_layout = self.abi.layout_version;
+        let layout_changed = previous_layout != next.abi.layout_version;
+        let rebuild = layout_changed || self.retired.len() >= MAX_RETIRED_LIBRARIES;
+
+        (self.on_unload)(game_memory, rebuild);
+        let previous = std::mem::replace(self, next);
+
+        if rebuild {
+            if layout_changed {
+                println!(
+                    "Game state layout changed ({:#x} -> {:#x}), migrating",
+                    previous_layout, self.abi.layout_version
+                );
+            } else {
+                println!(
+                    "Rebuilding the game state to unload {} previous game DLLs",
+                    previous.retired.len() + 1
+                );
             }
-            update = std::mem::transmute(func);
+            if !(self.migrate)(game_memory, previous_layout) {
+                eprintln!("Game DLL can't migrate the previous state, starting fresh");
+                game_memory.snapshot.clear();
+            }
+            // `previous` and its retired libraries are unloaded on return, the state is gone
+        } else {
+            self.retired = previous.retired;
+            self.retired.push(previous.library);
         }
+        (self.on_load)(game_memory);
+    }
 
-        return GameDll { handle, update };
+    /**
+     * Serializes the game state into GameMemory::snapshot (empty if the game isn't initialized yet).
+     */
+    pub fn save_state(&self, game_memory: &mut GameMemory) {
+        (self.save_state)(game_memory);
     }
 
+    /**
+     * Overwrites the game state with GameMemory::snapshot.
+     */
+    pub fn load_state(&self, game_memory: &mut GameMemory) {
+        (self.load_state)(game_memory);
+    }
+
+    fn unique_copy_path(build_path: &Path) -> PathBuf {
+        let stem = build_path.file_stem().unwrap().to_string_lossy();
+        let extension = build_path.extension().unwrap().to_string_lossy();
+        let copy = NEXT_COPY.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!(
+            "{}-{}-{}.{}",
+            stem,
+            std::process::id(),
+            copy,
+            extension
+        ))
+    }
+
+    /**
+     * Advances the simulation by one fixed timestep (Time::delta).
+     */
     pub fn update(
         &self,
         game_memory: &mut GameMemory,
-        batch: &mut Batch,
-        screen_target: &RenderTarget,
         keyboard: &Keyboard,
         mouse: &Mouse,
+        time: &Time,
         device: &Device,
     ) {
-        (self.update)(game_memory, batch, screen_target, keyboard, mouse, device);
+        (self.update)(game_memory, keyboard, mouse, time, device);
+    }
+
+    /**
+     * Draws the current state, may run any number of times between two updates.
+     */
+    pub fn render(
+        &self,
+        game_memory: &mut GameMemory,
+        batch: &mut Batch,
+        screen_target: &RenderTarget,
+        time: &Time,
+    ) {
+        (self.render)(game_memory, batch, screen_target, time);
+    }
+}
+
+impl Library {
+    fn load(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let c_path = CString::new(path.to_string_lossy().as_bytes())?;
+        let handle = unsafe { SDL_LoadObject(c_path.as_ptr()) };
+        if handle.is_null() {
+            let _ = std::fs::remove_file(&path);
+            return Err(format!("Failed to load {}", path.display()).into());
+        }
+        Ok(Library { handle, path })
+    }
+
+    /**
+     * `F` must be the extern "C" fn type the game exports under `name`.
+     */
+    fn symbol<F: Copy>(&self, name: &CStr) -> Result<F, Box<dyn Error>> {
+        let func: SDL_FunctionPointer = unsafe { SDL_LoadFunction(self.handle, name.as_ptr()) };
+        if func.is_none() {
+            return Err(format!("Failed to find symbol {:?}", name).into());
+        }
+        debug_assert_eq!(size_of::<F>(), size_of::<SDL_FunctionPointer>());
+        Ok(unsafe { std::mem::transmute_copy::<SDL_FunctionPointer, F>(&func) })
     }
 }
 
-impl Drop for GameDll {
+impl Drop for Library {
     fn drop(&mut self) {
         unsafe {
             SDL_UnloadObject(self.handle);
         }
+        let _ = std::fs::remove_file(&self.path);
     }
 }
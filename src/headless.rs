@@ -0,0 +1,490 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use common::{
+    Device, Keycode,
+    game_memory::GameMemory,
+    graphics::{
+        MAX_INDICES, MAX_VERTICES, batch::Batch, material::Material, recording::RecordedPass,
+        render_target::RenderTarget,
+    },
+    input::{keyboard::Keyboard, mouse::Mouse},
+    time::Time,
+    utils::png,
+};
+use sdl3::mouse::MouseButton;
+
+use crate::{FIXED_TIMESTEP, SCREEN_HEIGHT, SCREEN_WIDTH, game_dll::GameDll, recording::Recorder};
+
+const DEFAULT_FRAMES: u32 = 600;
+
+/**
+ * Runs the game without window, GPU or audio (CI machines, benchmarks):
+ *
+ *   sdl3_gpu --headless [--frames N] [--script input.txt] [--replay session.rec]
+ *                       [--dump-commands out.txt] [--golden expected.txt]
+ *                       [--screenshot out.png] [--reference expected.png] [--update-golden]
+ *
+ * Every frame goes through the same update_game as the windowed host, but Batch records
+ * its draws instead of submitting them. Frames are not paced, the game runs as fast as it can:
+ * each frame is exactly one fixed update followed by one render.
+ *
+ * The script feeds input on specific frames, one event per line ('#' starts a comment):
+ *
+ *   0   press D
+ *   45  release D
+ *   50  move 640 360
+ *   50  down left
+ *   51  up left
+ *   60  wheel 0 -1
+ *   120 quit
+ *
+ * --dump-commands writes every recorded draw of the run (see RecordedPass' text form),
+ * --golden compares them against a previous dump and fails on the first difference.
+ * A missing golden file is an error: --update-golden (over)writes it with this run instead.
+ *
+ * Offscreen render targets are rasterized on the CPU (see graphics::rasterizer).
+ * --screenshot saves the first one drawn on the last frame (the game's 320x180 game_target),
+ * --reference compares it pixel by pixel, --update-golden rewrites it the same way.
+ *
+ * Returns the process exit code: non-zero if --replay diverged or --golden / --reference didn't match.
+ */
+pub fn run(args: Vec<String>) -> i32 {
+    let mut frames = None;
+    let mut script = Script::default();
+    let mut dump_path: Option<PathBuf> = None;
+    let mut golden_path: Option<PathBuf> = None;
+    let mut screenshot_path: Option<PathBuf> = None;
+    let mut reference_path: Option<PathBuf> = None;
+    let mut update_golden = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--frames" => match iter.next().and_then(|n| n.parse().ok()) {
+                Some(n) => frames = Some(n),
+                None => {
+                    eprintln!("--frames expects a number");
+                    return 2;
+                }
+            },
+            "--script" => match iter.next().map(|path| Script::load(Path::new(path))) {
+                Some(Ok(loaded)) => script = loaded,
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    return 2;
+                }
+                None => {
+                    eprintln!("Missing file after --script");
+                    return 2;
+                }
+            },
+            "--dump-commands" | "--golden" | "--screenshot" | "--reference" => {
+                let Some(path) = iter.next() else {
+                    eprintln!("Missing file after {}", arg);
+                    return 2;
+                };
+                let path = Some(PathBuf::from(path));
+                match arg.as_str() {
+                    "--dump-commands" => dump_path = path,
+                    "--golden" => golden_path = path,
+                    "--screenshot" => screenshot_path = path,
+                    _ => reference_path = path,
+                }
+            }
+            "--update-golden" => update_golden = true,
+            _ => {}
+        }
+    }
+    let mut recorder = Recorder::from_args(args.into_iter());
+    // A replay ends the run by itself, don't cut it short unless asked to
+    let frames = frames.unwrap_or(if recorder.is_replaying() {
+        u32::MAX
+    } else {
+        DEFAULT_FRAMES
+    });
+
+    let device = Device::Headless;
+    let mut keyboard = Keyboard::default();
+    let mut mouse = Mouse::default();
+    let mut batch = Batch::new(device.clone(), Material::headless("default"));
+    let mut game_memory = GameMemory::default();
+    let gamedll = GameDll::load().expect("Unable to load game dll");
+    gamedll.start(&mut game_memory);
+    let mut screen_target = RenderTarget::empty();
+    screen_target.resize(SCREEN_WIDTH, SCREEN_HEIGHT);
+    screen_target.resize_pixels(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut time = Time::new(FIXED_TIMESTEP);
+    // Every render directly follows an update, there is nothing to interpolate
+    time.set_alpha(1f32);
+
+    let mut stats = Stats::default();
+    let mut commands = String::new();
+    let keep_commands = dump_path.is_some() || golden_path.is_some();
+    let mut last_frame: Vec<RecordedPass> = Vec::new();
+    let mut exit_code = 0;
+    let started = Instant::now();
+
+    for frame in 0..frames {
+        keyboard.clear_pressed();
+        mouse.clear_position_delta();
+        mouse.clear_button_pressed();
+        mouse.clear_wheel();
+        if script.apply(frame, &mut keyboard, &mut mouse) {
+            break;
+        }
+
+        if let Some(code) = recorder.frame(&gamedll, &mut game_memory, &mut keyboard, &mut mouse) {
+            exit_code = code;
+            break;
+        }
+
+        let frame_start = Instant::now();
+        gamedll.update(&mut game_memory, &keyboard, &mouse, &time, &device);
+        time.advance();
+        gamedll.render(&mut game_memory, &mut batch, &screen_target, &time);
+        let update_time = frame_start.elapsed();
+
+        let passes = batch.take_recorded_passes();
+        stats.add_frame(update_time, &passes);
+        if keep_commands {
+            writeln!(commands, "frame {}", frame).unwrap();
+            for pass in &passes {
+                write!(commands, "{}", pass).unwrap();
+            }
+        }
+        last_frame = passes;
+
+        if game_memory.quit {
+            break;
+        }
+    }
+
+    recorder.stop_recording(&gamedll, &mut game_memory);
+    stats.print(started.elapsed());
+
+    if let Some(path) = dump_path {
+        match std::fs::write(&path, &commands) {
+            Ok(()) => println!("Draw commands written to {}", path.display()),
+            Err(e) => {
+                eprintln!("Could not write {}: {}", path.display(), e);
+                exit_code = 1;
+            }
+        }
+    }
+    if let Some(path) = golden_path
+        && !compare_with_golden(&path, &commands, update_golden)
+    {
+        exit_code = 1;
+    }
+
+    if screenshot_path.is_some() || reference_path.is_some() {
+        let Some(target) = last_frame.iter().find_map(|pass| pass.target.as_ref()) else {
+            eprintln!("Nothing to screenshot: no offscreen target was drawn on the last frame");
+            return 1;
+        };
+        let pixels = target.pixels().expect("Headless targets live on the CPU");
+        let (width, height) = (target.width(), target.height());
+
+        if let Some(path) = screenshot_path {
+            match png::write_rgba(&path, width, height, &pixels) {
+                Ok(()) => println!("Screenshot written to {}", path.display()),
+                Err(e) => {
+                    eprintln!("Could not write {}: {}", path.display(), e);
+                    exit_code = 1;
+                }
+            }
+        }
+        if let Some(path) = reference_path
+            && !compare_with_reference(&path, width, height, &pixels, update_golden)
+        {
+            exit_code = 1;
+        }
+    }
+    return exit_code;
+}
+
+fn compare_with_reference(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    update: bool,
+) -> bool {
+    if update {
+        return update_golden_file(path, png::write_rgba(path, width, height, pixels));
+    }
+
+    let Some((reference_width, reference_height, reference)) = png::read_rgba(path) else {
+        eprintln!(
+            "Could not load reference image {} (--update-golden creates it)",
+            path.display()
+        );
+        return false;
+    };
+    if (reference_width, reference_height) != (width, height) {
+        eprintln!(
+            "Screenshot is {}x{}, reference {} is {}x{}",
+            width,
+            height,
+            path.display(),
+            reference_width,
+            reference_height
+        );
+        return false;
+    }
+
+    let differences: Vec<usize> = pixels
+        .chunks_exact(4)
+        .zip(reference.chunks_exact(4))
+        .enumerate()
+        .filter(|(_, (actual, expected))| actual != expected)
+        .map(|(index, _)| index)
+        .collect();
+    let Some(first) = differences.first() else {
+        println!("Screenshot matches {}", path.display());
+        return true;
+    };
+    let (x, y) = (first % width as usize, first / width as usize);
+    eprintln!(
+        "Screenshot differs from {} in {} pixels, first at ({}, {}): {:?}, expected {:?}",
+        path.display(),
+        differences.len(),
+        x,
+        y,
+        &pixels[first * 4..][..4],
+        &reference[first * 4..][..4]
+    );
+    return false;
+}
+
+fn compare_with_golden(path: &Path, actual: &str, update: bool) -> bool {
+    if update {
+        return update_golden_file(path, std::fs::write(path, actual));
+    }
+
+    let expected = match std::fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(e) => {
+            eprintln!(
+                "Could not read golden file {}: {} (--update-golden creates it)",
+                path.display(),
+                e
+            );
+            return false;
+        }
+    };
+    if expected == actual {
+        println!("Draw commands match {}", path.display());
+        return true;
+    }
+
+    let draw_count = |commands: &str| {
+        commands
+            .lines()
+            .filter(|line| line.starts_with("  draw"))
+            .count()
+    };
+    let (expected_draws, actual_draws) = (draw_count(&expected), draw_count(actual));
+    eprintln!(
+        "Draw commands differ from {}: {} draws, expected {} ({:+})",
+        path.display(),
+        actual_draws,
+        expected_draws,
+        actual_draws as i64 - expected_draws as i64
+    );
+
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut frame = "";
+    for line_number in 1.. {
+        let (expected_line, actual_line) = (expected_lines.next(), actual_lines.next());
+        if expected_line != actual_line {
+            eprintln!("  first difference at line {} ({}):", line_number, frame);
+            eprintln!("  - {}", expected_line.unwrap_or("<end of file>"));
+            eprintln!("  + {}", actual_line.unwrap_or("<end of file>"));
+            break;
+        }
+        if let Some(line) = actual_line
+            && line.starts_with("frame ")
+        {
+            frame = line;
+        }
+    }
+    return false;
+}
+
+// Reports how writing a golden file or reference image went
+fn update_golden_file(path: &Path, written: std::io::Result<()>) -> bool {
+    match written {
+        Ok(()) => {
+            println!("Updated {}", path.display());
+            true
+        }
+        Err(e) => {
+            eprintln!("Could not write {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+enum Action {
+    Press(Keycode),
+    Release(Keycode),
+    Move(f32, f32),
+    Down(MouseButton),
+    Up(MouseButton),
+    Wheel(f32, f32),
+    Quit,
+}
+
+/**
+ * Scripted input, sorted by frame.
+ */
+#[derive(Default)]
+struct Script {
+    events: Vec<(u32, Action)>,
+    next: usize,
+    // Mouse motion deltas are relative to the previous 'move'
+    mouse_position: (f32, f32),
+}
+
+impl Script {
+    fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+
+        let mut events = Vec::new();
+        for (number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let event = Self::parse_line(line).ok_or_else(|| {
+                format!(
+                    "{}:{}: invalid event '{}'",
+                    path.display(),
+                    number + 1,
+                    line
+                )
+            })?;
+            events.push(event);
+        }
+        // Stable: events on the same frame keep the order they were written in
+        events.sort_by_key(|(frame, _)| *frame);
+
+        return Ok(Script {
+            events,
+            ..Default::default()
+        });
+    }
+
+    fn parse_line(line: &str) -> Option<(u32, Action)> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let frame = words.first()?.parse().ok()?;
+        let word = |index: usize| words.get(index).copied();
+        let number = |index: usize| word(index)?.parse::<f32>().ok();
+
+        let action = match word(1)? {
+            "press" => Action::Press(Keycode::from_name(word(2)?)?),
+            "release" => Action::Release(Keycode::from_name(word(2)?)?),
+            "move" => Action::Move(number(2)?, number(3)?),
+            "down" => Action::Down(Self::parse_button(word(2)?)?),
+            "up" => Action::Up(Self::parse_button(word(2)?)?),
+            "wheel" => Action::Wheel(number(2)?, number(3)?),
+            "quit" => Action::Quit,
+            _ => return None,
+        };
+        return Some((frame, action));
+    }
+
+    fn parse_button(name: &str) -> Option<MouseButton> {
+        match name {
+            "left" => Some(MouseButton::Left),
+            "right" => Some(MouseButton::Right),
+            "middle" => Some(MouseButton::Middle),
+            _ => None,
+        }
+    }
+
+    /**
+     * Applies every event scheduled for `frame`. Returns true when the script asks to quit.
+     */
+    fn apply(&mut self, frame: u32, keyboard: &mut Keyboard, mouse: &mut Mouse) -> bool {
+        while let Some((at, action)) = self.events.get(self.next) {
+            if *at > frame {
+                break;
+            }
+            self.next += 1;
+            match action {
+                Action::Press(key) => keyboard.press(*key),
+                Action::Release(key) => keyboard.release(key),
+                Action::Move(x, y) => {
+                    let (previous_x, previous_y) = self.mouse_position;
+                    mouse.set_position(*x, *y, x - previous_x, y - previous_y);
+                    self.mouse_position = (*x, *y);
+                }
+                Action::Down(button) => mouse.mouse_button_down(*button),
+                Action::Up(button) => mouse.mouse_button_up(*button),
+                Action::Wheel(x, y) => mouse.set_wheel(*x, *y),
+                Action::Quit => return true,
+            }
+        }
+        return false;
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    frames: u32,
+    update_time: Duration,
+    slowest_frame: Duration,
+    passes: usize,
+    draws: usize,
+    vertices: usize,
+    // Largest single pass, against the GPU buffers' initial size
+    peak_vertices: usize,
+    peak_indices: usize,
+}
+
+impl Stats {
+    fn add_frame(&mut self, update_time: Duration, passes: &[RecordedPass]) {
+        self.frames += 1;
+        self.update_time += update_time;
+        self.slowest_frame = self.slowest_frame.max(update_time);
+        for pass in passes {
+            self.passes += 1;
+            self.draws += pass.draws.len();
+            self.vertices += pass.vertices.len();
+            self.peak_vertices = self.peak_vertices.max(pass.vertices.len());
+            self.peak_indices = self.peak_indices.max(pass.indices.len());
+        }
+    }
+
+    fn print(&self, total: Duration) {
+        let frames = self.frames.max(1);
+        println!("Headless run: {} frames in {:?}", self.frames, total);
+        println!(
+            "  update + render: {:?} avg, {:?} slowest",
+            self.update_time / frames,
+            self.slowest_frame
+        );
+        println!(
+            "  per frame: {:.1} passes, {:.1} draws, {:.1} vertices",
+            self.passes as f32 / frames as f32,
+            self.draws as f32 / frames as f32,
+            self.vertices as f32 / frames as f32
+        );
+        println!(
+            "  largest pass: {} vertices ({:.1}% of {}), {} indices ({:.1}% of {})",
+            self.peak_vertices,
+            self.peak_vertices as f32 * 100f32 / MAX_VERTICES as f32,
+            MAX_VERTICES,
+            self.peak_indices,
+            self.peak_indices as f32 * 100f32 / MAX_INDICES as f32,
+            MAX_INDICES
+        );
+    }
+}
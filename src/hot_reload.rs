@@ -0,0 +1,130 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub enum BuildEvent {
+    Started,
+    Finished(Result<(), String>),
+}
+
+/**
+ * Watches the game sources and rebuilds the game dll on a background thread.
+ *
+ * The frame loop never waits on cargo: it calls `poll()` once per frame and
+ * reloads the GameDll when a build finishes successfully.
+ *
+ *   main thread                    watcher thread
+ *   -----------                    --------------
+ *   request_build() ------------>  (or game/src changed)
+ *                                  cargo build -p game
+ *   poll() -> Finished(Ok) <-----  BuildEvent::Finished
+ *   GameDll::load()
+ */
+pub struct HotReload {
+    requests: Sender<()>,
+    events: Receiver<BuildEvent>,
+}
+
+impl HotReload {
+    pub fn watch() -> Self {
+        let workspace = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let (requests, request_receiver) = mpsc::channel();
+        let (event_sender, events) = mpsc::channel();
+
+        thread::Builder::new()
+            .name("hot-reload".to_string())
+            .spawn(move || watch_and_build(workspace, request_receiver, event_sender))
+            .expect("Unable to spawn hot-reload thread");
+
+        HotReload { requests, events }
+    }
+
+    /**
+     * Rebuilds the game even if no source file changed.
+     */
+    pub fn request_build(&self) {
+        let _ = self.requests.send(());
+    }
+
+    /**
+     * Non-blocking, returns the next pending build event (if any).
+     */
+    pub fn poll(&self) -> Option<BuildEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+fn watch_and_build(workspace: PathBuf, requests: Receiver<()>, events: Sender<BuildEvent>) {
+    let sources = workspace.join("game").join("src");
+    let mut last_modified = latest_modification(&sources);
+
+    loop {
+        let requested = match requests.recv_timeout(POLL_INTERVAL) {
+            Ok(()) => true,
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => return, // HotReload was dropped
+        };
+
+        let modified = latest_modification(&sources);
+        if !requested && modified <= last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        if events.send(BuildEvent::Started).is_err() {
+            return;
+        }
+        let result = build_game(&workspace);
+        if events.send(BuildEvent::Finished(result)).is_err() {
+            return;
+        }
+    }
+}
+
+/**
+ * Most recent modification time of any file under `dir` (recursive).
+ * Editors often save by replacing files, so we compare timestamps instead of tracking inodes.
+ */
+fn latest_modification(dir: &Path) -> SystemTime {
+    let mut latest = SystemTime::UNIX_EPOCH;
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return latest;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let modified = if path.is_dir() {
+            latest_modification(&path)
+        } else {
+            entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        };
+        latest = latest.max(modified);
+    }
+    latest
+}
+
+fn build_game(workspace: &Path) -> Result<(), String> {
+    let mut command = Command::new("cargo");
+    command.arg("build").arg("--package").arg("game");
+    if !cfg!(debug_assertions) {
+        command.arg("--release");
+    }
+
+    let output = command
+        .current_dir(workspace)
+        .output()
+        .map_err(|e| format!("Could not run cargo: {}", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+    }
+    Ok(())
+}
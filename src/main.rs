@@ -1,37 +1,53 @@
+mod capture;
 mod game_dll;
+mod headless;
+mod hot_reload;
+mod recording;
 
+use common::Device;
 use common::game_memory::GameMemory;
 use common::input::keyboard::Keyboard;
 use common::input::mouse::Mouse;
+use common::time::Time;
 use sdl3::event::{Event, WindowEvent};
 use sdl3::gpu::ShaderFormat;
-use sdl3::keyboard::Keycode;
-use std::process::Command;
+use sdl3::keyboard::{Keycode, Mod};
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 extern crate nalgebra_glm as glm;
 
+use crate::capture::{Capture, CaptureSource};
 use crate::game_dll::GameDll;
+use crate::hot_reload::{BuildEvent, HotReload};
+use crate::recording::{DEFAULT_RECORDING, Recorder};
 
 use common::graphics::batch::Batch;
 use common::graphics::material::Material;
 use common::graphics::render_target::RenderTarget;
 
-pub const FPS: u64 = 60;
-pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FPS);
+pub const UPDATES_PER_SECOND: u64 = 60;
+pub const FIXED_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / UPDATES_PER_SECOND);
+// After a long stall (breakpoint, window drag) skip ahead instead of replaying every missed update
+pub const MAX_UPDATES_PER_FRAME: u64 = 5;
 
 pub const SCREEN_WIDTH: u32 = 320 * 4;
 pub const SCREEN_HEIGHT: u32 = 180 * 4;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--headless") {
+        std::process::exit(headless::run(args));
+    }
+
     // Give us access to windowing and input events
     let sdl_context = sdl3::init().unwrap();
 
     // Windowing
     let video_subsystem = sdl_context.video().expect("Unable to get video subsystem");
-    let window = video_subsystem
+    let mut window = video_subsystem
         .window("Game", SCREEN_WIDTH, SCREEN_HEIGHT)
-        // .resizable() // TODO: uniform scaling (int)
+        .resizable()
         .high_pixel_density()
         .position_centered()
         .build()
@@ -43,27 +59,42 @@ fn main() {
     let mut event_pump = sdl_context.event_pump().expect("Unable to get event pump");
 
     // GPU
-    let device = sdl3::gpu::Device::new(
-        // TODO: Support more shader formats.
+    let gpu_device = sdl3::gpu::Device::new(
+        // The formats our shaders ship in (see ShaderSpecification), SDL picks the backend from them.
+        // TODO: DXIL, compile.sh doesn't produce it yet so Windows runs on Vulkan.
         ShaderFormat::MSL | ShaderFormat::SPIRV,
         cfg!(debug_assertions),
     )
     .expect("Unable to create GPU device")
     .with_window(&window) // Attach to window
     .expect("Unable to attach GPU device to window");
+    let device = Device::Gpu(gpu_device.clone());
+    let swapchain_format = gpu_device.get_swapchain_texture_format(&window);
 
     let mut batch = Batch::new(device.clone(), Material::default(device.clone(), &window));
     let mut game_memory = GameMemory::default();
-    let mut gamedll = GameDll::load();
+    let mut gamedll = GameDll::load().expect("Unable to load game dll");
+    gamedll.start(&mut game_memory);
+    let hot_reload = HotReload::watch();
+    let mut recorder = Recorder::from_args(args.into_iter());
+    let mut capture = Capture::new();
     let mut screen_target = RenderTarget::empty();
     screen_target.resize(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let (pixel_width, pixel_height) = window.size_in_pixels();
+    screen_target.resize_pixels(pixel_width, pixel_height);
+
+    let mut time = Time::new(FIXED_TIMESTEP);
+    let mut accumulator = Duration::ZERO;
+    let mut previous_frame = Instant::now();
+    let mut fullscreen = false;
 
+    // Frames are paced by the swapchain (vsync), updates by the accumulator:
+    // the simulation runs at UPDATES_PER_SECOND whatever the display refresh rate.
     'running: loop {
-        let start = Instant::now();
-        keyboard.clear_pressed();
-        mouse.clear_position_delta();
-        mouse.clear_button_pressed();
-        mouse.clear_wheel();
+        let now = Instant::now();
+        accumulator += now - previous_frame;
+        previous_frame = now;
+
         for event in event_pump.poll_iter() {
             match event {
                 Event::Window {
@@ -71,23 +102,52 @@ fn main() {
                     window_id: _,
                     win_event: WindowEvent::Resized(width, height),
                 } => screen_target.resize(width as u32, height as u32),
+                Event::Window {
+                    timestamp: _,
+                    window_id: _,
+                    win_event: WindowEvent::PixelSizeChanged(width, height),
+                } => screen_target.resize_pixels(width as u32, height as u32),
                 Event::Quit { .. } => break 'running,
                 Event::KeyDown {
                     keycode: Some(Keycode::R),
                     ..
+                } => hot_reload.request_build(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    ..
+                } => recorder.toggle_recording(
+                    Path::new(DEFAULT_RECORDING),
+                    &gamedll,
+                    &mut game_memory,
+                ),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    ..
+                } => recorder.toggle_loop(Path::new(DEFAULT_RECORDING)),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    keymod,
+                    ..
+                } => capture.request_screenshot(
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        CaptureSource::GameTarget
+                    } else {
+                        CaptureSource::Screen
+                    },
+                ),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    ..
+                } => capture.toggle_sequence(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    ..
                 } => {
-                    // Compile the dll
-                    // Hot-reload the game .dll
-                    let result = compile_dll_in_dir("/Users/feresr/Workspace/learn_sdl3_gpu/game");
-                    match result {
-                        Ok(_) => {
-                            println!("Game DLL reloaded");
-                            gamedll = GameDll::load();
-                        }
-                        Err(e) => {
-                            eprintln!("Could not recompile game.dll: {}", e);
-                        }
-                    }
+                    // The window sends a Resized event once it is (or leaves) fullscreen
+                    fullscreen = !fullscreen;
+                    window
+                        .set_fullscreen(fullscreen)
+                        .expect("Unable to toggle fullscreen");
                 }
                 Event::KeyDown {
                     keycode: Some(kc), ..
@@ -106,68 +166,84 @@ fn main() {
                 _ => {}
             }
         }
-        let mut cmd = device.acquire_command_buffer().unwrap();
-        let texture = cmd.wait_and_acquire_swapchain_texture(&window).unwrap();
 
-        screen_target.set_texture(texture);
-        gamedll.update(
-            &mut game_memory,
-            &mut batch,
-            &screen_target,
-            &keyboard,
-            &mouse,
-            &device,
-        );
-        screen_target.clear_texture();
-
-        cmd.submit().unwrap();
-        
-        if game_memory.quit {
-            break 'running;
+        // Builds run on the hot-reload thread, the frame loop only picks up the result
+        while let Some(event) = hot_reload.poll() {
+            match event {
+                BuildEvent::Started => println!("Rebuilding game DLL..."),
+                BuildEvent::Finished(Ok(())) => match GameDll::load() {
+                    Ok(reloaded) => {
+                        gamedll.replace(reloaded, &mut game_memory);
+                        println!("Game DLL reloaded");
+                    }
+                    Err(e) => eprintln!("Could not load game DLL: {}", e),
+                },
+                BuildEvent::Finished(Err(e)) => {
+                    eprintln!("Could not recompile game DLL:");
+                    eprintln!("{}", e);
+                }
+            }
         }
 
-        precise_sleep(start);
-    }
-
-    fn precise_sleep(start: Instant) {
-        let sleep_until = start + FRAME_DURATION;
-        let now = Instant::now();
-        if now < sleep_until {
-            let sleep_duration = sleep_until - now;
-
-            // Sleep most of the time, but not all (wake up 1 milli early)
-            if sleep_duration > Duration::from_millis(1) {
-                std::thread::sleep(sleep_duration - Duration::from_millis(1));
+        let mut updates = 0;
+        while accumulator >= FIXED_TIMESTEP {
+            if updates == MAX_UPDATES_PER_FRAME {
+                let skipped = accumulator.as_nanos() / FIXED_TIMESTEP.as_nanos();
+                time.drop_frames(skipped as u64);
+                accumulator = Duration::from_nanos(
+                    (accumulator.as_nanos() % FIXED_TIMESTEP.as_nanos()) as u64,
+                );
+                break;
             }
 
-            // Spin for the remaining time for precision
-            while Instant::now() < sleep_until {
-                std::hint::spin_loop();
-            }
-        } else {
-            #[cfg(debug_assertions)]
+            // Overrides live input while a recording is playing
+            if let Some(exit_code) =
+                recorder.frame(&gamedll, &mut game_memory, &mut keyboard, &mut mouse)
             {
-                // TODO: Panic in debug on frame drop
-                // let elapsed = (sleep_until - now).subsec_millis();
-                // println!("Running under slee_until elapsed {:?}", elapsed);
+                std::process::exit(exit_code);
             }
-        }
-    }
 
-    fn compile_dll_in_dir(project_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let output = Command::new("cargo")
-            .arg("build")
-            // .arg("--release") // TODO
-            .current_dir(project_path) // Set working directory
-            .output()?;
-
-        if !output.status.success() {
-            eprintln!("Cargo build failed:");
-            eprintln!("{}", String::from_utf8_lossy(&output.stderr));
-            return Err("Build failed".into());
+            gamedll.update(&mut game_memory, &keyboard, &mouse, &time, &device);
+            time.advance();
+            accumulator -= FIXED_TIMESTEP;
+            updates += 1;
+
+            // One time actions (presses, clicks, wheel) are seen by exactly one update
+            keyboard.clear_pressed();
+            mouse.clear_position_delta();
+            mouse.clear_button_pressed();
+            mouse.clear_wheel();
+
+            if game_memory.quit {
+                break 'running;
+            }
         }
+        if updates > 1 {
+            // Only the last of these updates gets rendered
+            time.drop_frames(updates - 1);
+        }
+        time.set_alpha(accumulator.as_secs_f32() / FIXED_TIMESTEP.as_secs_f32());
+
+        let mut cmd = gpu_device.acquire_command_buffer().unwrap();
+        let texture = cmd.wait_and_acquire_swapchain_texture(&window).unwrap();
 
-        println!("Build successful!");
-        Ok(())
+        screen_target.set_texture(texture, swapchain_format);
+        gamedll.render(&mut game_memory, &mut batch, &screen_target, &time);
+        capture.before_submit(&gpu_device, &screen_target, &cmd);
+        screen_target.clear_texture();
+
+        // Presents the swapchain texture, after the copies capture recorded into cmd
+        if capture.waits_for_frame() {
+            let fence = cmd.submit_and_acquire_fence(&gpu_device).unwrap();
+            gpu_device
+                .wait_fences(true, &[fence])
+                .expect("Could not wait for the frame");
+        } else {
+            cmd.submit().unwrap();
+        }
+        capture.after_submit(&gpu_device, &game_memory);
     }
+
+    // Keep whatever was recorded so far replayable
+    recorder.stop_recording(&gamedll, &mut game_memory);
 }
@@ -0,0 +1,459 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use common::{
+    game_memory::GameMemory,
+    input::{keyboard::Keyboard, mouse::Mouse},
+    utils::fnv1a,
+};
+
+use crate::game_dll::GameDll;
+
+/**
+ * Input recording file:
+ *
+ * [magic: "GREC"][version: u32][start state len: u32][start state..]
+ * [frame len: u32][keyboard snapshot..][mouse snapshot..]   (once per update)
+ * [0: u32][end state checksum: u64]                         (missing if the recording was cut short)
+ *
+ * The start state is the game's own snapshot (GameDll::save_state), not a raw copy of
+ * GameMemory::storage: the storage holds pointers into the heap that don't survive a rewind.
+ */
+const MAGIC: &[u8; 4] = b"GREC";
+const VERSION: u32 = 1;
+
+pub const DEFAULT_RECORDING: &str = "session.rec";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Replay {
+    // Play once, then verify the end state against the recording
+    Once,
+    // Rewind to the start state and play again, forever
+    Loop,
+}
+
+enum Request {
+    Record(PathBuf),
+    Replay(PathBuf, Replay),
+}
+
+enum Mode {
+    Idle,
+    Recording(Recording),
+    Playing(Playback),
+}
+
+/**
+ * Records the input of every fixed update to a file and plays it back update-for-update.
+ *
+ * Recording and playback both need an initialized game (the start state comes from it),
+ * so requests made before the first update are deferred until the game is ready.
+ */
+pub struct Recorder {
+    mode: Mode,
+    pending: Option<Request>,
+    // Set when launched with --replay: exit with the verification result once done
+    exit_after_replay: bool,
+}
+
+impl Recorder {
+    /**
+     * --record <file>   record from the first frame until F5 is pressed or the game quits
+     * --replay <file>   play back once, verify the end state and exit (non-zero on mismatch)
+     * --loop <file>     play back in a loop
+     */
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut recorder = Recorder {
+            mode: Mode::Idle,
+            pending: None,
+            exit_after_replay: false,
+        };
+        while let Some(arg) = args.next() {
+            let request = match arg.as_str() {
+                "--record" => args.next().map(|path| Request::Record(path.into())),
+                "--replay" => {
+                    recorder.exit_after_replay = true;
+                    args.next()
+                        .map(|path| Request::Replay(path.into(), Replay::Once))
+                }
+                "--loop" => args
+                    .next()
+                    .map(|path| Request::Replay(path.into(), Replay::Loop)),
+                _ => continue,
+            };
+            match request {
+                Some(request) => recorder.pending = Some(request),
+                None => eprintln!("Missing file after {}", arg),
+            }
+        }
+        recorder
+    }
+
+    /**
+     * True while a recording is playing or about to start playing.
+     */
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, Mode::Playing(_)) || matches!(self.pending, Some(Request::Replay(..)))
+    }
+
+    pub fn toggle_recording(
+        &mut self,
+        path: &Path,
+        gamedll: &GameDll,
+        game_memory: &mut GameMemory,
+    ) {
+        if let Mode::Recording(_) = self.mode {
+            self.stop_recording(gamedll, game_memory);
+        } else {
+            self.pending = Some(Request::Record(path.to_path_buf()));
+        }
+    }
+
+    /**
+     * Writes the end state and closes the file. No-op unless recording.
+     */
+    pub fn stop_recording(&mut self, gamedll: &GameDll, game_memory: &mut GameMemory) {
+        let Mode::Recording(recording) = std::mem::replace(&mut self.mode, Mode::Idle) else {
+            return;
+        };
+        let path = recording.path.clone();
+        gamedll.save_state(game_memory);
+        match recording.finish(&game_memory.snapshot) {
+            Ok(frames) => println!("Recorded {} frames to {}", frames, path.display()),
+            Err(e) => eprintln!("Could not finish recording {}: {}", path.display(), e),
+        }
+        game_memory.snapshot.clear();
+    }
+
+    pub fn toggle_loop(&mut self, path: &Path) {
+        match self.mode {
+            Mode::Playing(_) => {
+                self.mode = Mode::Idle;
+                println!("Playback stopped");
+            }
+            _ => self.pending = Some(Request::Replay(path.to_path_buf(), Replay::Loop)),
+        }
+    }
+
+    /**
+     * Invoked before every fixed update, after input events were polled.
+     * While playing, `keyboard` and `mouse` are overwritten with the recorded input.
+     * Returns an exit code once a --replay session is over.
+     */
+    pub fn frame(
+        &mut self,
+        gamedll: &GameDll,
+        game_memory: &mut GameMemory,
+        keyboard: &mut Keyboard,
+        mouse: &mut Mouse,
+    ) -> Option<i32> {
+        if game_memory.initialized
+            && let Some(request) = self.pending.take()
+        {
+            self.start(request, gamedll, game_memory);
+        }
+
+        match &mut self.mode {
+            Mode::Idle => {}
+            Mode::Recording(recording) => {
+                if let Err(e) = recording.record(keyboard, mouse) {
+                    eprintln!("Recording stopped: {}", e);
+                    self.mode = Mode::Idle;
+                }
+            }
+            Mode::Playing(playback) => {
+                if playback.next_frame(keyboard, mouse) {
+                    return None;
+                }
+                match playback.replay {
+                    Replay::Loop => {
+                        playback.rewind(gamedll, game_memory);
+                        playback.next_frame(keyboard, mouse);
+                    }
+                    Replay::Once => {
+                        let matches = playback.verify(gamedll, game_memory);
+                        self.mode = Mode::Idle;
+                        if self.exit_after_replay {
+                            return Some(if matches { 0 } else { 1 });
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn start(&mut self, request: Request, gamedll: &GameDll, game_memory: &mut GameMemory) {
+        match request {
+            Request::Record(path) => {
+                gamedll.save_state(game_memory);
+                match Recording::start(&path, &game_memory.snapshot) {
+                    Ok(recording) => {
+                        println!("Recording input to {}", path.display());
+                        self.mode = Mode::Recording(recording);
+                    }
+                    Err(e) => eprintln!("Could not record to {}: {}", path.display(), e),
+                }
+                game_memory.snapshot.clear();
+            }
+            Request::Replay(path, replay) => match Playback::open(&path, replay) {
+                Ok(mut playback) => {
+                    println!("Replaying {}", path.display());
+                    playback.rewind(gamedll, game_memory);
+                    self.mode = Mode::Playing(playback);
+                }
+                Err(e) => {
+                    eprintln!("Could not replay {}: {}", path.display(), e);
+                    if self.exit_after_replay {
+                        std::process::exit(1);
+                    }
+                }
+            },
+        }
+    }
+}
+
+struct Recording {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    frames: u32,
+    buffer: Vec<u8>,
+}
+
+impl Recording {
+    fn start(path: &Path, start_state: &[u8]) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(start_state.len() as u32).to_le_bytes())?;
+        writer.write_all(start_state)?;
+        Ok(Recording {
+            path: path.to_path_buf(),
+            writer,
+            frames: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    fn record(&mut self, keyboard: &Keyboard, mouse: &Mouse) -> io::Result<()> {
+        self.buffer.clear();
+        keyboard.write_snapshot(&mut self.buffer);
+        mouse.write_snapshot(&mut self.buffer);
+        self.writer
+            .write_all(&(self.buffer.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&self.buffer)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn finish(mut self, end_state: &[u8]) -> io::Result<u32> {
+        self.writer.write_all(&0u32.to_le_bytes())?;
+        self.writer.write_all(&fnv1a(end_state).to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(self.frames)
+    }
+}
+
+struct Playback {
+    replay: Replay,
+    start_state: Vec<u8>,
+    frames: Vec<Vec<u8>>,
+    end_checksum: Option<u64>,
+    next: usize,
+}
+
+impl Playback {
+    fn open(path: &Path, replay: Replay) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Self::parse(&bytes, replay)
+    }
+
+    fn parse(bytes: &[u8], replay: Replay) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Malformed recording");
+
+        if bytes.get(..4) != Some(MAGIC.as_slice()) || read_u32(bytes, 4) != Some(VERSION) {
+            return Err(invalid());
+        }
+        let start_len = read_u32(bytes, 8).ok_or_else(invalid)? as usize;
+        let mut offset = 12;
+        let start_state = bytes
+            .get(offset..offset + start_len)
+            .ok_or_else(invalid)?
+            .to_vec();
+        offset += start_len;
+
+        let mut frames = Vec::new();
+        let mut end_checksum = None;
+        while let Some(len) = read_u32(bytes, offset) {
+            offset += 4;
+            if len == 0 {
+                end_checksum = read_u64(bytes, offset);
+                break;
+            }
+            let Some(frame) = bytes.get(offset..offset + len as usize) else {
+                break; // Recording was cut short, play what we have
+            };
+            frames.push(frame.to_vec());
+            offset += len as usize;
+        }
+
+        Ok(Playback {
+            replay,
+            start_state,
+            frames,
+            end_checksum,
+            next: 0,
+        })
+    }
+
+    fn rewind(&mut self, gamedll: &GameDll, game_memory: &mut GameMemory) {
+        game_memory.snapshot.clear();
+        game_memory.snapshot.extend_from_slice(&self.start_state);
+        gamedll.load_state(game_memory);
+        self.next = 0;
+    }
+
+    /**
+     * Returns false once every recorded frame was played.
+     */
+    fn next_frame(&mut self, keyboard: &mut Keyboard, mouse: &mut Mouse) -> bool {
+        let Some(frame) = self.frames.get(self.next) else {
+            return false;
+        };
+        self.next += 1;
+        let restored = keyboard
+            .read_snapshot(frame)
+            .and_then(|read| mouse.read_snapshot(&frame[read..]));
+        if restored.is_none() {
+            eprintln!("Malformed input on frame {}", self.next - 1);
+        }
+        true
+    }
+
+    fn verify(&self, gamedll: &GameDll, game_memory: &mut GameMemory) -> bool {
+        gamedll.save_state(game_memory);
+        let actual = fnv1a(&game_memory.snapshot);
+        game_memory.snapshot.clear();
+
+        match self.end_checksum {
+            Some(expected) if expected == actual => {
+                println!("Replay OK ({} frames)", self.frames.len());
+                true
+            }
+            Some(expected) => {
+                eprintln!(
+                    "Replay diverged: end state {:#018x}, recorded {:#018x}",
+                    actual, expected
+                );
+                false
+            }
+            None => {
+                eprintln!("Replay finished, but the recording has no end state to compare");
+                false
+            }
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use common::Keycode;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("recording-{}-{}.rec", std::process::id(), name))
+    }
+
+    fn input(key: Keycode, x: f32) -> (Keyboard, Mouse) {
+        let mut keyboard = Keyboard::default();
+        keyboard.press(key);
+        let mut mouse = Mouse::default();
+        mouse.set_position(x, 20f32, x, 0f32);
+        (keyboard, mouse)
+    }
+
+    fn snapshot(keyboard: &Keyboard, mouse: &Mouse) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        keyboard.write_snapshot(&mut bytes);
+        mouse.write_snapshot(&mut bytes);
+        bytes
+    }
+
+    #[test]
+    fn plays_back_what_was_recorded() {
+        let path = temp_path("round-trip");
+        let frames = [input(Keycode::D, 10f32), input(Keycode::W, 30f32)];
+        let mut recording = Recording::start(&path, b"start").unwrap();
+        for (keyboard, mouse) in &frames {
+            recording.record(keyboard, mouse).unwrap();
+        }
+        assert_eq!(recording.finish(b"end").unwrap(), 2);
+
+        let mut playback = Playback::open(&path, Replay::Once).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(playback.start_state, b"start");
+        assert_eq!(playback.end_checksum, Some(fnv1a(b"end")));
+
+        let (mut keyboard, mut mouse) = (Keyboard::default(), Mouse::default());
+        for (expected_keyboard, expected_mouse) in &frames {
+            assert!(playback.next_frame(&mut keyboard, &mut mouse));
+            assert_eq!(
+                snapshot(&keyboard, &mouse),
+                snapshot(expected_keyboard, expected_mouse)
+            );
+        }
+        assert!(!playback.next_frame(&mut keyboard, &mut mouse));
+    }
+
+    #[test]
+    fn cut_short_recordings_play_their_complete_frames() {
+        let (keyboard, mouse) = input(Keycode::A, 0f32);
+        let frame = snapshot(&keyboard, &mouse);
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        for _ in 0..2 {
+            bytes.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&frame);
+        }
+        bytes.truncate(bytes.len() - 1);
+
+        let playback = Playback::parse(&bytes, Replay::Once).unwrap();
+        assert!(playback.start_state.is_empty());
+        assert_eq!(playback.frames, vec![frame]);
+        assert_eq!(playback.end_checksum, None);
+    }
+
+    #[test]
+    fn rejects_other_files_and_versions() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(VERSION + 1).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        assert!(Playback::parse(&bytes, Replay::Once).is_err());
+        assert!(Playback::parse(b"\x89PNG\r\n\x1a\n", Replay::Once).is_err());
+        // Start state longer than the file
+        let mut truncated = bytes[..4].to_vec();
+        truncated.extend_from_slice(&VERSION.to_le_bytes());
+        truncated.extend_from_slice(&16u32.to_le_bytes());
+        assert!(Playback::parse(&truncated, Replay::Once).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use std::{path::Path, process::Command};
+
+/**
+ * Plays tests/sessions/walk_and_jump.txt headlessly for FRAMES frames and checks what was drawn
+ * against the baselines in tests/golden. After an intended rendering change, rewrite them with:
+ *
+ *   sdl3_gpu --headless --script tests/sessions/walk_and_jump.txt --frames 30 \
+ *            --golden tests/golden/walk_and_jump.txt --reference tests/golden/walk_and_jump.png \
+ *            --update-golden
+ *
+ * Runs against the game dll `cargo build --workspace` left in target/, build it first.
+ */
+const FRAMES: &str = "30";
+
+fn run_headless(args: &[&str]) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(env!("CARGO_BIN_EXE_sdl3_gpu"))
+        .current_dir(root)
+        .args(["--headless", "--script", "tests/sessions/walk_and_jump.txt"])
+        .args(["--frames", FRAMES])
+        .args(args)
+        .output()
+        .expect("Could not run the headless host");
+    assert!(
+        output.status.success(),
+        "Headless run {:?} failed:\n{}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn draw_commands_match_the_golden_file() {
+    run_headless(&["--golden", "tests/golden/walk_and_jump.txt"]);
+}
+
+#[test]
+fn last_frame_matches_the_reference_image() {
+    run_headless(&["--reference", "tests/golden/walk_and_jump.png"]);
+}
@@ -0,0 +1,40 @@
+use std::{path::Path, process::Command};
+
+/**
+ * Replays every recorded session in tests/sessions headlessly (see recording.rs): each has to
+ * end in the state it was recorded with. A failure means the simulation changed, re-record
+ * the session from its .txt script if that was intended:
+ *
+ *   sdl3_gpu --headless --script tests/sessions/<name>.txt --record tests/sessions/<name>.rec
+ *
+ * Runs against the game dll `cargo build --workspace` left in target/, build it first.
+ */
+#[test]
+fn recorded_sessions_replay_to_their_end_state() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let sessions: Vec<_> = std::fs::read_dir(root.join("tests/sessions"))
+        .expect("Missing tests/sessions")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "rec"))
+        .collect();
+    assert!(
+        !sessions.is_empty(),
+        "No recorded sessions in tests/sessions"
+    );
+
+    for session in sessions {
+        let output = Command::new(env!("CARGO_BIN_EXE_sdl3_gpu"))
+            .current_dir(root)
+            .arg("--headless")
+            .arg("--replay")
+            .arg(&session)
+            .output()
+            .expect("Could not run the headless host");
+        assert!(
+            output.status.success(),
+            "{} diverged:\n{}",
+            session.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}